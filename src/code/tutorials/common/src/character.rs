@@ -0,0 +1,561 @@
+//! Character controller, that is shared by the players of the tutorial games. The controller decides
+//! how the character moves every frame: it accelerates and decelerates the character, keeps it on
+//! the ground when walking down slopes and stairs, climbs small steps, slides along walls and
+//! carries the character with moving platforms. Physics is only used to apply the gravity and to
+//! resolve the contacts that are left.
+//!
+//! The same controller works in 2D and 3D, the Y axis is always "up". 2D games use the XY plane.
+
+use fyrox::{
+    core::{
+        algebra::{Point2, Point3, Vector2, Vector3},
+        log::Log,
+        pool::Handle,
+        reflect::prelude::*,
+        variable::InheritableVariable,
+        visitor::prelude::*,
+    },
+    graph::SceneGraph,
+    scene::{
+        collider::{Collider, ColliderShape},
+        dim2,
+        graph::{physics::RayCastOptions, Graph},
+        node::Node,
+        rigidbody::RigidBody,
+    },
+};
+
+// Distance (in meters) between the feet and the ground, that is still considered as standing on
+// the ground. Smaller gaps are not corrected to avoid jittering on contacts.
+const GROUND_TOLERANCE: f32 = 0.05;
+
+// Walls and steps are probed slightly above the feet, otherwise the ground itself would be hit.
+const STEP_CLEARANCE: f32 = 0.05;
+
+// ANCHOR: character_physics
+/// A hit of a ray, that was cast by the controller.
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub position: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub collider: Handle<Node>,
+    pub distance: f32,
+}
+
+/// Physical queries, that the controller needs. It is implemented for 3D and 2D physics.
+pub trait CharacterPhysics {
+    /// Casts a ray and returns the closest hit with anything, except the character itself.
+    fn cast_ray(&self, origin: Vector3<f32>, direction: Vector3<f32>, max_len: f32) -> Option<Hit>;
+
+    /// Velocity of the body, that the collider is attached to. Characters standing on moving
+    /// platforms are carried with this velocity.
+    fn body_velocity(&self, collider: Handle<Node>) -> Vector3<f32>;
+}
+
+// Colliders of the character are attached to its rigid body.
+fn is_own_collider(graph: &Graph, collider: Handle<Node>, character: Handle<Node>) -> bool {
+    graph
+        .try_get(collider)
+        .is_ok_and(|collider| collider.parent() == character)
+}
+
+pub struct Physics3d<'a> {
+    graph: &'a Graph,
+    character: Handle<Node>,
+}
+
+impl<'a> Physics3d<'a> {
+    /// `character` is the rigid body of the character, its colliders are ignored.
+    pub fn new(graph: &'a Graph, character: Handle<Node>) -> Self {
+        Self { graph, character }
+    }
+}
+
+impl CharacterPhysics for Physics3d<'_> {
+    fn cast_ray(&self, origin: Vector3<f32>, direction: Vector3<f32>, max_len: f32) -> Option<Hit> {
+        let mut intersections = Vec::new();
+        self.graph.physics.cast_ray(
+            RayCastOptions {
+                ray_origin: Point3::from(origin),
+                ray_direction: direction,
+                max_len,
+                groups: Default::default(),
+                sort_results: true,
+            },
+            &mut intersections,
+        );
+
+        intersections
+            .into_iter()
+            .find(|i| !is_own_collider(self.graph, i.collider, self.character))
+            .map(|i| Hit {
+                position: i.position.coords,
+                normal: i.normal,
+                collider: i.collider,
+                distance: i.toi,
+            })
+    }
+
+    fn body_velocity(&self, collider: Handle<Node>) -> Vector3<f32> {
+        self.graph
+            .try_get(collider)
+            .ok()
+            .and_then(|collider| {
+                self.graph
+                    .try_get_of_type::<RigidBody>(collider.parent())
+                    .ok()
+            })
+            .map(|body| body.lin_vel())
+            .unwrap_or_default()
+    }
+}
+
+pub struct Physics2d<'a> {
+    graph: &'a Graph,
+    character: Handle<Node>,
+}
+
+impl<'a> Physics2d<'a> {
+    /// `character` is the rigid body of the character, its colliders are ignored.
+    pub fn new(graph: &'a Graph, character: Handle<Node>) -> Self {
+        Self { graph, character }
+    }
+}
+
+impl CharacterPhysics for Physics2d<'_> {
+    fn cast_ray(&self, origin: Vector3<f32>, direction: Vector3<f32>, max_len: f32) -> Option<Hit> {
+        let mut intersections = Vec::new();
+        self.graph.physics2d.cast_ray(
+            dim2::physics::RayCastOptions {
+                ray_origin: Point2::new(origin.x, origin.y),
+                ray_direction: direction.xy(),
+                max_len,
+                groups: Default::default(),
+                sort_results: true,
+            },
+            &mut intersections,
+        );
+
+        intersections
+            .into_iter()
+            .find(|i| !is_own_collider(self.graph, i.collider, self.character))
+            .map(|i| Hit {
+                position: Vector3::new(i.position.x, i.position.y, origin.z),
+                normal: Vector3::new(i.normal.x, i.normal.y, 0.0),
+                collider: i.collider,
+                distance: i.toi,
+            })
+    }
+
+    fn body_velocity(&self, collider: Handle<Node>) -> Vector3<f32> {
+        self.graph
+            .try_get(collider)
+            .ok()
+            .and_then(|collider| {
+                self.graph
+                    .try_get_of_type::<dim2::rigidbody::RigidBody>(collider.parent())
+                    .ok()
+            })
+            .map(|body| {
+                let velocity = body.lin_vel();
+                Vector3::new(velocity.x, velocity.y, 0.0)
+            })
+            .unwrap_or_default()
+    }
+}
+// ANCHOR_END: character_physics
+
+// ANCHOR: character_shape
+/// The part of the shape of the character, that the controller cares about.
+#[derive(Debug, Clone, Copy)]
+pub struct CharacterShape {
+    /// The lowest point of the character (in world coordinates).
+    pub feet: Vector3<f32>,
+    /// Horizontal size of the character.
+    pub radius: f32,
+}
+
+impl CharacterShape {
+    /// Takes the shape from a capsule, ball or cuboid collider of a 3D character.
+    pub fn from_collider(graph: &Graph, collider: Handle<Node>) -> Option<Self> {
+        let collider = graph.try_get_of_type::<Collider>(collider).ok()?;
+        let (feet, radius) = match collider.shape() {
+            ColliderShape::Capsule(capsule) => {
+                let bottom = if capsule.begin.y <= capsule.end.y {
+                    capsule.begin
+                } else {
+                    capsule.end
+                };
+                (bottom - Vector3::y().scale(capsule.radius), capsule.radius)
+            }
+            ColliderShape::Ball(ball) => (-Vector3::y().scale(ball.radius), ball.radius),
+            ColliderShape::Cuboid(cuboid) => (
+                -Vector3::y().scale(cuboid.half_extents.y),
+                cuboid.half_extents.x.max(cuboid.half_extents.z),
+            ),
+            _ => return None,
+        };
+        Some(Self {
+            feet: collider
+                .global_transform()
+                .transform_point(&Point3::from(feet))
+                .coords,
+            radius,
+        })
+    }
+
+    /// Takes the shape from a capsule, ball or cuboid collider of a 2D character.
+    pub fn from_collider_2d(graph: &Graph, collider: Handle<Node>) -> Option<Self> {
+        let collider = graph
+            .try_get_of_type::<dim2::collider::Collider>(collider)
+            .ok()?;
+        let (feet, radius) = match collider.shape() {
+            dim2::collider::ColliderShape::Capsule(capsule) => {
+                let bottom = if capsule.begin.y <= capsule.end.y {
+                    capsule.begin
+                } else {
+                    capsule.end
+                };
+                (bottom - Vector2::y().scale(capsule.radius), capsule.radius)
+            }
+            dim2::collider::ColliderShape::Ball(ball) => {
+                (-Vector2::y().scale(ball.radius), ball.radius)
+            }
+            dim2::collider::ColliderShape::Cuboid(cuboid) => (
+                -Vector2::y().scale(cuboid.half_extents.y),
+                cuboid.half_extents.x,
+            ),
+            _ => return None,
+        };
+        Some(Self {
+            feet: collider
+                .global_transform()
+                .transform_point(&Point3::new(feet.x, feet.y, 0.0))
+                .coords,
+            radius,
+        })
+    }
+}
+/// Returns `collider` if it is a 3D or 2D collider, otherwise the first collider attached to the
+/// rigid body of the character. Scenes, that were made before the character controller, don't
+/// have the collider assigned. Returns [`Handle::NONE`] if the character has no colliders at all,
+/// such characters move without the controller. Both fallbacks are logged as warnings.
+pub fn find_collider(
+    graph: &Graph,
+    character: Handle<Node>,
+    collider: Handle<Node>,
+) -> Handle<Node> {
+    let is_collider = |handle: Handle<Node>| {
+        graph.try_get_of_type::<Collider>(handle).is_ok()
+            || graph
+                .try_get_of_type::<dim2::collider::Collider>(handle)
+                .is_ok()
+    };
+
+    if is_collider(collider) {
+        return collider;
+    }
+
+    let Ok(character) = graph.try_get(character) else {
+        return Handle::NONE;
+    };
+    match character
+        .children()
+        .iter()
+        .copied()
+        .find(|child| is_collider(*child))
+    {
+        Some(collider) => {
+            Log::warn(format!(
+                "The collider of {} is not set, the first collider of its body is used.",
+                character.name()
+            ));
+            collider
+        }
+        None => {
+            Log::warn(format!(
+                "{} has no collider, it moves without the character controller.",
+                character.name()
+            ));
+            Handle::NONE
+        }
+    }
+}
+// ANCHOR_END: character_shape
+
+// ANCHOR: character_input
+/// What the character wants to do in the current frame.
+#[derive(Debug, Clone, Default)]
+pub struct CharacterInput {
+    /// Horizontal velocity (in meters per second), that the character wants to move with.
+    pub desired_velocity: Vector3<f32>,
+    /// Initial vertical speed of a jump, if the character wants to jump. The character could jump
+    /// only from the ground.
+    pub jump_speed: Option<f32>,
+}
+
+/// Result of the controller update, it should be applied to the rigid body of the character.
+#[derive(Debug, Clone, Default)]
+pub struct Movement {
+    pub velocity: Vector3<f32>,
+    /// Instant vertical correction of the position, that puts the character on a step or snaps it
+    /// to the ground.
+    pub offset: Vector3<f32>,
+}
+
+impl Movement {
+    /// Applies the movement to a 3D rigid body, that is attached to the root of the scene.
+    pub fn apply(&self, body: &mut RigidBody) {
+        body.set_lin_vel(self.velocity);
+        if self.offset != Vector3::zeros() {
+            let transform = body.local_transform_mut();
+            let position = **transform.position();
+            transform.set_position(position + self.offset);
+        }
+    }
+
+    /// Applies the movement to a 2D rigid body, that is attached to the root of the scene.
+    pub fn apply_2d(&self, body: &mut dim2::rigidbody::RigidBody) {
+        body.set_lin_vel(self.velocity.xy());
+        if self.offset != Vector3::zeros() {
+            let transform = body.local_transform_mut();
+            let position = **transform.position();
+            transform.set_position(position + self.offset);
+        }
+    }
+}
+// ANCHOR_END: character_input
+
+// Changes the vector towards the target by at most `max_delta`.
+fn move_towards(current: Vector3<f32>, target: Vector3<f32>, max_delta: f32) -> Vector3<f32> {
+    let delta = target - current;
+    let distance = delta.norm();
+    if distance <= max_delta || distance <= f32::EPSILON {
+        target
+    } else {
+        current + delta.scale(max_delta / distance)
+    }
+}
+
+fn horizontal(vector: Vector3<f32>) -> Vector3<f32> {
+    Vector3::new(vector.x, 0.0, vector.z)
+}
+
+// ANCHOR: character_controller
+#[derive(Visit, Reflect, Debug, Clone)]
+#[visit(optional)]
+pub struct CharacterController {
+    // How fast (in m/s²) the character reaches the desired speed on the ground.
+    acceleration: InheritableVariable<f32>,
+
+    // How fast (in m/s²) the character stops on the ground when there's no input.
+    friction: InheritableVariable<f32>,
+
+    // A part of the acceleration, that the character has in the air.
+    air_control: InheritableVariable<f32>,
+
+    // The steepest slope (in degrees), that the character could walk on. Steeper slopes are walls.
+    max_slope: InheritableVariable<f32>,
+
+    // The highest obstacle (in meters), that the character steps on without jumping.
+    step_height: InheritableVariable<f32>,
+
+    // The character sticks to the ground, that is at most this far below it, so it doesn't fly
+    // off when walking down slopes and stairs.
+    snap_distance: InheritableVariable<f32>,
+
+    // Own velocity of the character (without the velocity of the platform).
+    #[reflect(hidden)]
+    velocity: Vector3<f32>,
+
+    #[reflect(hidden)]
+    on_ground: bool,
+
+    // A collider under the feet of the character.
+    #[reflect(hidden)]
+    ground: Handle<Node>,
+
+    // The character is moving up after a jump, the ground is ignored until it starts falling.
+    #[reflect(hidden)]
+    jumping: bool,
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        Self {
+            acceleration: 40.0.into(),
+            friction: 30.0.into(),
+            air_control: 0.2.into(),
+            max_slope: 45.0.into(),
+            step_height: 0.3.into(),
+            snap_distance: 0.3.into(),
+            velocity: Default::default(),
+            on_ground: false,
+            ground: Default::default(),
+            jumping: false,
+        }
+    }
+}
+
+impl CharacterController {
+    pub fn is_on_ground(&self) -> bool {
+        self.on_ground
+    }
+
+    /// A collider under the feet of the character, it is valid only when the character is on the
+    /// ground.
+    pub fn ground(&self) -> Handle<Node> {
+        self.ground
+    }
+
+    /// Own velocity of the character, without gravity and the velocity of the platform.
+    pub fn velocity(&self) -> Vector3<f32> {
+        self.velocity
+    }
+
+    /// Drops the own velocity of the character, for example when it dies or respawns.
+    pub fn stop(&mut self) {
+        self.velocity = Vector3::zeros();
+    }
+
+    fn is_walkable(&self, normal: &Vector3<f32>) -> bool {
+        normal.dot(&Vector3::y()) >= self.max_slope.to_radians().cos()
+    }
+
+    /// Calculates the movement of the character for the current frame. `body_velocity` is the
+    /// current velocity of the rigid body of the character.
+    pub fn update(
+        &mut self,
+        physics: &impl CharacterPhysics,
+        shape: CharacterShape,
+        input: CharacterInput,
+        body_velocity: Vector3<f32>,
+        dt: f32,
+    ) -> Movement {
+        let up = Vector3::y();
+        let step_height = *self.step_height;
+
+        // ANCHOR: ground_check
+        // Jumps end at the top, after that the character could land again.
+        if self.jumping && body_velocity.y <= 0.0 {
+            self.jumping = false;
+        }
+
+        // The ray starts at the step height, so it finds the ground even if the feet sank into it.
+        let max_gap = if self.on_ground {
+            *self.snap_distance
+        } else {
+            GROUND_TOLERANCE
+        };
+        let ground_hit = physics.cast_ray(
+            shape.feet + up.scale(step_height),
+            -up,
+            step_height + max_gap,
+        );
+        let (ground, steep_ground) = match ground_hit {
+            Some(hit) if !self.jumping && self.is_walkable(&hit.normal) => (Some(hit), None),
+            Some(hit) if !self.jumping => (None, Some(hit)),
+            _ => (None, None),
+        };
+        self.on_ground = ground.is_some();
+        self.ground = ground.as_ref().map(|hit| hit.collider).unwrap_or_default();
+        // ANCHOR_END: ground_check
+
+        // ANCHOR: acceleration
+        let target = horizontal(input.desired_velocity);
+        let rate = if !self.on_ground {
+            *self.acceleration * *self.air_control
+        } else if target.norm_squared() > 0.0 {
+            *self.acceleration
+        } else {
+            *self.friction
+        };
+        self.velocity = move_towards(horizontal(self.velocity), target, rate * dt);
+        // ANCHOR_END: acceleration
+
+        // ANCHOR: walls_and_steps
+        let mut offset = Vector3::zeros();
+        let speed = self.velocity.norm();
+        if let Some(direction) = self.velocity.try_normalize(f32::EPSILON) {
+            let look_ahead = shape.radius + speed * dt;
+            let knee = shape.feet + up.scale(step_height + STEP_CLEARANCE);
+            let ankle = shape.feet + up.scale(STEP_CLEARANCE);
+
+            if let Some(wall) = physics.cast_ray(knee, direction, look_ahead) {
+                // The obstacle is too high to step on it, slide along it.
+                if !self.is_walkable(&wall.normal) {
+                    self.slide(&wall.normal);
+                }
+            } else if let Some(step) = physics
+                .cast_ray(ankle, direction, look_ahead)
+                .filter(|step| self.on_ground && !self.is_walkable(&step.normal))
+            {
+                // Find the top of the step right behind its edge and put the character on it.
+                let above_step = knee + direction.scale(step.distance + STEP_CLEARANCE);
+                if let Some(top) = physics
+                    .cast_ray(above_step, -up, step_height + STEP_CLEARANCE)
+                    .filter(|top| self.is_walkable(&top.normal))
+                {
+                    offset.y = (top.position.y - shape.feet.y).max(0.0);
+                }
+            }
+        }
+
+        // Steep slopes are walls as well, the gravity makes the character slide down.
+        if let Some(steep_ground) = steep_ground {
+            self.slide(&steep_ground.normal);
+        }
+        // ANCHOR_END: walls_and_steps
+
+        // ANCHOR: vertical_movement
+        let Some(ground) = ground else {
+            // The physics takes care of falling.
+            return Movement {
+                velocity: self.velocity + up.scale(body_velocity.y),
+                offset,
+            };
+        };
+
+        let platform_velocity = physics.body_velocity(ground.collider);
+
+        if let Some(jump_speed) = input.jump_speed {
+            self.on_ground = false;
+            self.jumping = true;
+            return Movement {
+                velocity: self.velocity + platform_velocity + up.scale(jump_speed),
+                offset,
+            };
+        }
+
+        // Walk along the slope instead of bumping into it or flying off it.
+        let slope_speed = if ground.normal.y > f32::EPSILON {
+            -self.velocity.dot(&ground.normal) / ground.normal.y
+        } else {
+            0.0
+        };
+
+        // Put the feet right on the ground, whether they're above it or sank into it.
+        let gap = ground.distance - step_height;
+        if gap.abs() > GROUND_TOLERANCE && offset.y == 0.0 {
+            offset.y = -gap;
+        }
+
+        Movement {
+            velocity: self.velocity + platform_velocity + up.scale(slope_speed),
+            offset,
+        }
+        // ANCHOR_END: vertical_movement
+    }
+
+    // Removes the part of the velocity, that goes into the obstacle.
+    fn slide(&mut self, normal: &Vector3<f32>) {
+        let Some(normal) = horizontal(*normal).try_normalize(f32::EPSILON) else {
+            return;
+        };
+        let into_obstacle = self.velocity.dot(&normal);
+        if into_obstacle < 0.0 {
+            self.velocity -= normal.scale(into_obstacle);
+        }
+    }
+}
+// ANCHOR_END: character_controller
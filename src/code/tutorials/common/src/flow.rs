@@ -0,0 +1,366 @@
+//! Game flow: main menu, loading, playing, pause and game over states. The flow owns the level
+//! scene and a simple menu, games only react to the changes of the state.
+
+use crate::input::Binding;
+use fyrox::{
+    core::{color::Color, log::Log, pool::Handle, reflect::prelude::*, visitor::prelude::*},
+    engine::GraphicsContext,
+    event::Event,
+    graph::SceneGraph,
+    gui::{
+        border::{Border, BorderBuilder},
+        brush::Brush,
+        button::{Button, ButtonBuilder, ButtonMessage},
+        message::UiMessage,
+        stack_panel::StackPanelBuilder,
+        text::{Text, TextBuilder, TextMessage},
+        widget::{WidgetBuilder, WidgetMessage},
+        BuildContext, HorizontalAlignment, Thickness, UserInterface, VerticalAlignment,
+    },
+    plugin::{error::GameResult, Plugin, PluginContext},
+    scene::{
+        animation::{absm::AnimationBlendingStateMachine, AnimationPlayer},
+        node::Node,
+        particle_system::ParticleSystem,
+        Scene,
+    },
+    window::CursorGrabMode,
+};
+
+// ANCHOR: game_state
+#[derive(Visit, Reflect, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameState {
+    #[default]
+    MainMenu,
+    Loading,
+    Playing,
+    Paused,
+    GameOver,
+}
+// ANCHOR_END: game_state
+
+// ANCHOR: flow_plugin
+/// A plugin, that uses the game flow. The flow needs to access itself from scene loading callbacks,
+/// that receive the plugin.
+pub trait FlowPlugin: Plugin {
+    fn flow_mut(&mut self) -> &mut GameFlow;
+
+    /// Called when the level is loaded and added to the engine, right before the game starts.
+    fn on_level_loaded(&mut self, _ctx: &mut PluginContext) -> GameResult {
+        Ok(())
+    }
+}
+// ANCHOR_END: flow_plugin
+
+fn make_button(text: &str, ctx: &mut BuildContext) -> Handle<Button> {
+    ButtonBuilder::new(
+        WidgetBuilder::new()
+            .with_width(200.0)
+            .with_height(32.0)
+            .with_margin(Thickness::uniform(4.0)),
+    )
+    .with_text(text)
+    .build(ctx)
+}
+
+#[derive(Visit, Reflect, Default, Debug, Clone)]
+struct Menu {
+    root: Handle<Border>,
+    title: Handle<Text>,
+    play: Handle<Button>,
+    resume: Handle<Button>,
+    restart: Handle<Button>,
+    main_menu: Handle<Button>,
+    quit: Handle<Button>,
+}
+
+impl Menu {
+    fn new(ui: &mut UserInterface) -> Self {
+        let ctx = &mut ui.build_ctx();
+
+        let title = TextBuilder::new(WidgetBuilder::new().with_margin(Thickness::uniform(4.0)))
+            .with_horizontal_text_alignment(HorizontalAlignment::Center)
+            .build(ctx);
+        let play = make_button("Play", ctx);
+        let resume = make_button("Resume", ctx);
+        let restart = make_button("Restart", ctx);
+        let main_menu = make_button("Main Menu", ctx);
+        let quit = make_button("Quit", ctx);
+
+        let root = BorderBuilder::new(
+            WidgetBuilder::new()
+                .with_background(Brush::Solid(Color::from_rgba(0, 0, 0, 160)).into())
+                .with_child(
+                    StackPanelBuilder::new(
+                        WidgetBuilder::new()
+                            .with_horizontal_alignment(HorizontalAlignment::Center)
+                            .with_vertical_alignment(VerticalAlignment::Center)
+                            .with_child(title)
+                            .with_child(play)
+                            .with_child(resume)
+                            .with_child(restart)
+                            .with_child(main_menu)
+                            .with_child(quit),
+                    )
+                    .build(ctx),
+                ),
+        )
+        .build(ctx);
+
+        Self {
+            root,
+            title,
+            play,
+            resume,
+            restart,
+            main_menu,
+            quit,
+        }
+    }
+
+    fn sync(&self, ui: &UserInterface, state: GameState, message: &str) {
+        let title = match state {
+            GameState::MainMenu => "Main Menu",
+            GameState::Loading => "Loading...",
+            GameState::Playing => "",
+            GameState::Paused => "Paused",
+            GameState::GameOver => message,
+        };
+        ui.send(self.title, TextMessage::Text(title.to_string()));
+
+        let in_menu = matches!(state, GameState::Paused | GameState::GameOver);
+        ui.send(
+            self.root,
+            WidgetMessage::Visibility(state != GameState::Playing),
+        );
+        ui.send(
+            self.play,
+            WidgetMessage::Visibility(state == GameState::MainMenu),
+        );
+        ui.send(
+            self.resume,
+            WidgetMessage::Visibility(state == GameState::Paused),
+        );
+        ui.send(self.restart, WidgetMessage::Visibility(in_menu));
+        ui.send(self.main_menu, WidgetMessage::Visibility(in_menu));
+        ui.send(
+            self.quit,
+            WidgetMessage::Visibility(state != GameState::Loading),
+        );
+    }
+}
+
+// ANCHOR: game_flow
+#[derive(Visit, Reflect, Default, Debug, Clone)]
+pub struct GameFlow {
+    state: GameState,
+    level_path: String,
+    scene: Handle<Scene>,
+    // A message, that is shown in the game over menu.
+    game_over_message: String,
+    menu: Menu,
+    // Animations and particle systems, that were stopped by the menu. Only they are started again
+    // when the game goes on.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    paused_nodes: Vec<Handle<Node>>,
+}
+
+impl GameFlow {
+    pub fn new(level_path: &str) -> Self {
+        Self {
+            level_path: level_path.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn state(&self) -> GameState {
+        self.state
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.state == GameState::Playing
+    }
+
+    /// Handle of the current level, it is `NONE` in the main menu and while loading.
+    pub fn scene(&self) -> Handle<Scene> {
+        self.scene
+    }
+
+    /// Creates the menu and either shows it or starts the level immediately. The latter is used
+    /// when the game is launched from the editor with a specific scene.
+    pub fn init<P: FlowPlugin>(&mut self, scene_path: Option<&str>, ctx: &mut PluginContext) {
+        self.menu = Menu::new(ctx.user_interfaces.first_mut());
+
+        if let Some(scene_path) = scene_path {
+            self.level_path = scene_path.to_string();
+            self.start_level::<P>(ctx);
+        } else {
+            self.set_state(GameState::MainMenu, ctx);
+        }
+    }
+
+    // ANCHOR: start_level
+    /// Loads the level from scratch, the current level (if any) is unloaded. Used to restart
+    /// the level as well.
+    pub fn start_level<P: FlowPlugin>(&mut self, ctx: &mut PluginContext) {
+        let level_path = self.level_path.clone();
+        self.load_level::<P>(&level_path, ctx);
+    }
+
+    /// Loads the given scene instead of the level, for example a saved game. Restart still loads
+    /// the level from scratch.
+    pub fn load_level<P: FlowPlugin>(&mut self, path: &str, ctx: &mut PluginContext) {
+        self.unload_level(ctx);
+        self.set_state(GameState::Loading, ctx);
+
+        ctx.load_scene(path, false, |result, plugin: &mut P, ctx| {
+            let flow = plugin.flow_mut();
+            flow.scene = ctx.scenes.add(result?.payload);
+            flow.set_state(GameState::Playing, ctx);
+            plugin.on_level_loaded(ctx)
+        });
+    }
+    // ANCHOR_END: start_level
+
+    pub fn unload_level(&mut self, ctx: &mut PluginContext) {
+        if self.scene.is_some() {
+            ctx.scenes.remove(self.scene);
+            self.scene = Handle::NONE;
+        }
+        self.paused_nodes.clear();
+    }
+
+    pub fn pause(&mut self, ctx: &mut PluginContext) {
+        if self.state == GameState::Playing {
+            self.set_state(GameState::Paused, ctx);
+        }
+    }
+
+    pub fn resume(&mut self, ctx: &mut PluginContext) {
+        if self.state == GameState::Paused {
+            self.set_state(GameState::Playing, ctx);
+        }
+    }
+
+    /// Stops the game and shows the game over menu with the given message (for example, "You
+    /// Died" or "Victory").
+    pub fn game_over(&mut self, message: &str, ctx: &mut PluginContext) {
+        if self.state == GameState::Playing {
+            self.game_over_message = message.to_string();
+            self.set_state(GameState::GameOver, ctx);
+        }
+    }
+
+    pub fn go_to_main_menu(&mut self, ctx: &mut PluginContext) {
+        self.unload_level(ctx);
+        self.set_state(GameState::MainMenu, ctx);
+    }
+
+    // ANCHOR: set_state
+    fn set_state(&mut self, state: GameState, ctx: &mut PluginContext) {
+        self.state = state;
+
+        let is_playing = state == GameState::Playing;
+
+        // The world stays in place while the menu is opened. Scripts check the state on their own.
+        if let Ok(scene) = ctx.scenes.try_get_mut(self.scene) {
+            self.freeze_scene(!is_playing, scene);
+        }
+
+        // The cursor is used to control the camera while playing and to click buttons otherwise.
+        if let GraphicsContext::Initialized(ref graphics_context) = ctx.graphics_context {
+            graphics_context.window.set_cursor_visible(!is_playing);
+            Log::verify(graphics_context.window.set_cursor_grab(if is_playing {
+                CursorGrabMode::Confined
+            } else {
+                CursorGrabMode::None
+            }));
+        }
+
+        self.menu.sync(
+            ctx.user_interfaces.first_mut(),
+            state,
+            &self.game_over_message,
+        );
+    }
+
+    // Stops physics, sounds, animations and particle systems of the level, or starts them again.
+    fn freeze_scene(&mut self, frozen: bool, scene: &mut Scene) {
+        let graph = &mut scene.graph;
+        graph.physics.enabled.set_value_and_mark_modified(!frozen);
+        graph.sound_context.state().pause(frozen);
+
+        if !frozen {
+            for handle in std::mem::take(&mut self.paused_nodes) {
+                if let Ok(particle_system) = graph.try_get_mut_of_type::<ParticleSystem>(handle) {
+                    particle_system.play(true);
+                } else if let Ok(node) = graph.try_get_mut(handle) {
+                    node.set_enabled(true);
+                }
+            }
+            return;
+        }
+
+        // Disabled nodes are not updated, but animation players and state machines have nothing
+        // to render, so they are simply disabled. Particle systems stop emitting, while the
+        // existing particles stay visible.
+        let handles = graph.pair_iter().map(|(h, _)| h).collect::<Vec<_>>();
+        for handle in handles {
+            if let Ok(particle_system) = graph.try_get_mut_of_type::<ParticleSystem>(handle) {
+                if particle_system.is_playing() {
+                    particle_system.play(false);
+                    self.paused_nodes.push(handle);
+                }
+                continue;
+            }
+            let is_animation = graph.try_get_of_type::<AnimationPlayer>(handle).is_ok()
+                || graph
+                    .try_get_of_type::<AnimationBlendingStateMachine>(handle)
+                    .is_ok();
+            let node = &mut graph[handle];
+            if is_animation && node.is_enabled() {
+                node.set_enabled(false);
+                self.paused_nodes.push(handle);
+            }
+        }
+    }
+    // ANCHOR_END: set_state
+
+    // ANCHOR: flow_events
+    /// Toggles the pause, when any of the given bindings is pressed.
+    pub fn on_os_event(
+        &mut self,
+        event: &Event<()>,
+        pause_bindings: &[Binding],
+        ctx: &mut PluginContext,
+    ) {
+        let Some(binding) = Binding::from_event(event) else {
+            return;
+        };
+
+        if pause_bindings.contains(&binding) {
+            match self.state {
+                GameState::Playing => self.pause(ctx),
+                GameState::Paused => self.resume(ctx),
+                _ => (),
+            }
+        }
+    }
+
+    pub fn on_ui_message<P: FlowPlugin>(&mut self, message: &UiMessage, ctx: &mut PluginContext) {
+        if let Some(ButtonMessage::Click) = message.data_from(self.menu.play) {
+            self.start_level::<P>(ctx);
+        } else if let Some(ButtonMessage::Click) = message.data_from(self.menu.resume) {
+            self.resume(ctx);
+        } else if let Some(ButtonMessage::Click) = message.data_from(self.menu.restart) {
+            self.start_level::<P>(ctx);
+        } else if let Some(ButtonMessage::Click) = message.data_from(self.menu.main_menu) {
+            self.go_to_main_menu(ctx);
+        } else if let Some(ButtonMessage::Click) = message.data_from(self.menu.quit) {
+            ctx.loop_controller.exit();
+        }
+    }
+    // ANCHOR_END: flow_events
+}
+// ANCHOR_END: game_flow
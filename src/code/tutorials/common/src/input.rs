@@ -0,0 +1,243 @@
+//! Rebindable input actions. Scripts query named actions and axes instead of hard-coded keys, the
+//! actual bindings are stored in a RON file and could be changed at runtime.
+
+use fyrox::{
+    core::log::Log,
+    engine::input::InputState,
+    event::{ElementState, Event, MouseButton, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, path::Path};
+
+// ANCHOR: binding
+/// A physical button, that could trigger an action.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl Binding {
+    /// Extracts a binding from a key or mouse button press. Useful to capture a new binding when a
+    /// player rebinds an action in the settings menu.
+    pub fn from_event(event: &Event<()>) -> Option<Self> {
+        let Event::WindowEvent { event, .. } = event else {
+            return None;
+        };
+
+        match event {
+            // Held keys repeat the press, only the first one counts.
+            WindowEvent::KeyboardInput { event, .. }
+                if event.state == ElementState::Pressed && !event.repeat =>
+            {
+                match event.physical_key {
+                    PhysicalKey::Code(code) => Some(Self::Key(code)),
+                    _ => None,
+                }
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button,
+                ..
+            } => Some(Self::Mouse(*button)),
+            _ => None,
+        }
+    }
+
+    pub fn is_down(&self, input: &InputState) -> bool {
+        match self {
+            Binding::Key(code) => input.is_key_down(*code),
+            Binding::Mouse(button) => input.is_mouse_button_down(*button),
+        }
+    }
+
+    pub fn is_pressed(&self, input: &InputState) -> bool {
+        match self {
+            Binding::Key(code) => input.is_key_pressed(*code),
+            Binding::Mouse(button) => input.is_mouse_button_pressed(*button),
+        }
+    }
+
+    pub fn is_released(&self, input: &InputState) -> bool {
+        match self {
+            Binding::Key(code) => input.is_key_released(*code),
+            Binding::Mouse(button) => input.is_mouse_button_released(*button),
+        }
+    }
+}
+// ANCHOR_END: binding
+
+// ANCHOR: axis_binding
+/// A source of an analog value. Axes made of buttons produce values in `-1.0..=1.0` range, mouse
+/// axes produce mouse movement (in pixels) multiplied by the sensitivity.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum AxisBinding {
+    Buttons {
+        negative: Binding,
+        positive: Binding,
+    },
+    MouseX {
+        sensitivity: f32,
+    },
+    MouseY {
+        sensitivity: f32,
+    },
+}
+
+impl AxisBinding {
+    pub fn value(&self, input: &InputState) -> f32 {
+        match self {
+            AxisBinding::Buttons { negative, positive } => {
+                let mut value = 0.0;
+                if negative.is_down(input) {
+                    value -= 1.0;
+                }
+                if positive.is_down(input) {
+                    value += 1.0;
+                }
+                value
+            }
+            AxisBinding::MouseX { sensitivity } => input.mouse_speed().x * *sensitivity,
+            AxisBinding::MouseY { sensitivity } => input.mouse_speed().y * *sensitivity,
+        }
+    }
+}
+// ANCHOR_END: axis_binding
+
+#[derive(Debug)]
+pub enum InputMapError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for InputMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputMapError::Io(err) => write!(f, "unable to access the input config: {err}"),
+            InputMapError::Parse(err) => write!(f, "unable to parse the input config: {err}"),
+            InputMapError::Serialize(err) => {
+                write!(f, "unable to serialize the input config: {err}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for InputMapError {}
+
+impl From<std::io::Error> for InputMapError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for InputMapError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        Self::Parse(err)
+    }
+}
+
+impl From<ron::Error> for InputMapError {
+    fn from(err: ron::Error) -> Self {
+        Self::Serialize(err)
+    }
+}
+
+// ANCHOR: input_map
+/// A set of named actions and axes. An action could have any number of bindings, it is considered
+/// pressed if any of them is pressed. Values of multiple axis bindings are summed.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct InputMap {
+    #[serde(default)]
+    pub actions: BTreeMap<String, Vec<Binding>>,
+    #[serde(default)]
+    pub axes: BTreeMap<String, Vec<AxisBinding>>,
+}
+
+impl InputMap {
+    pub fn with_action(mut self, action: &str, bindings: impl Into<Vec<Binding>>) -> Self {
+        self.actions.insert(action.to_string(), bindings.into());
+        self
+    }
+
+    pub fn with_axis(mut self, axis: &str, bindings: impl Into<Vec<AxisBinding>>) -> Self {
+        self.axes.insert(axis.to_string(), bindings.into());
+        self
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, InputMapError> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(ron::from_str(&text)?)
+    }
+
+    /// Loads the bindings from the given file. Actions and axes, that are missing in the file, are
+    /// taken from the defaults, so an outdated config does not leave new actions unbound.
+    pub fn load_or(path: impl AsRef<Path>, defaults: InputMap) -> Self {
+        let path = path.as_ref();
+        match Self::load(path) {
+            Ok(mut map) => {
+                for (action, bindings) in defaults.actions {
+                    map.actions.entry(action).or_insert(bindings);
+                }
+                for (axis, bindings) in defaults.axes {
+                    map.axes.entry(axis).or_insert(bindings);
+                }
+                map
+            }
+            Err(err) => {
+                Log::warn(format!(
+                    "Unable to load input bindings from {}: {err}. Default bindings will be used.",
+                    path.display()
+                ));
+                defaults
+            }
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), InputMapError> {
+        let text = ron::ser::to_string_pretty(self, Default::default())?;
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Replaces all bindings of the action.
+    pub fn rebind(&mut self, action: &str, bindings: impl Into<Vec<Binding>>) {
+        self.actions.insert(action.to_string(), bindings.into());
+    }
+
+    /// Replaces all bindings of the axis.
+    pub fn rebind_axis(&mut self, axis: &str, bindings: impl Into<Vec<AxisBinding>>) {
+        self.axes.insert(axis.to_string(), bindings.into());
+    }
+
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.actions
+            .get(action)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns `true` while any binding of the action is held down.
+    pub fn is_pressed(&self, action: &str, input: &InputState) -> bool {
+        self.bindings(action).iter().any(|b| b.is_down(input))
+    }
+
+    /// Returns `true` only on the frame when any binding of the action was pressed.
+    pub fn is_just_pressed(&self, action: &str, input: &InputState) -> bool {
+        self.bindings(action).iter().any(|b| b.is_pressed(input))
+    }
+
+    /// Returns `true` only on the frame when any binding of the action was released.
+    pub fn is_just_released(&self, action: &str, input: &InputState) -> bool {
+        self.bindings(action).iter().any(|b| b.is_released(input))
+    }
+
+    pub fn axis(&self, axis: &str, input: &InputState) -> f32 {
+        self.axes
+            .get(axis)
+            .map(|bindings| bindings.iter().map(|b| b.value(input)).sum())
+            .unwrap_or_default()
+    }
+}
+// ANCHOR_END: input_map
@@ -0,0 +1,498 @@
+//! Interaction with the world: players look at an object and press a key to use it. Usable objects
+//! (doors, switches, elevators, etc.) are scripts, that include the [`Interaction`] component and
+//! implement the [`Interactable`] trait.
+
+use fyrox::{
+    core::{
+        algebra::{Point3, UnitQuaternion, Vector3},
+        log::Log,
+        pool::Handle,
+        reflect::prelude::*,
+        type_traits::prelude::*,
+        variable::InheritableVariable,
+        visitor::prelude::*,
+    },
+    graph::SceneGraph,
+    plugin::error::GameResult,
+    scene::{
+        animation::AnimationPlayer,
+        collider::Collider,
+        graph::{physics::RayCastOptions, Graph},
+        node::Node,
+        rigidbody::RigidBody,
+    },
+    script::{
+        ScriptContext, ScriptMessageContext, ScriptMessagePayload, ScriptMessageSender, ScriptTrait,
+    },
+};
+
+// ANCHOR: interaction_messages
+/// Sent to an interactable object, when someone uses it.
+#[derive(Debug)]
+pub struct InteractMessage {
+    pub user: Handle<Node>,
+}
+impl ScriptMessagePayload for InteractMessage {}
+
+/// Sent by switches to the nodes, that are linked to them.
+#[derive(Debug)]
+pub struct SwitchMessage {
+    pub user: Handle<Node>,
+    pub on: bool,
+}
+impl ScriptMessagePayload for SwitchMessage {}
+// ANCHOR_END: interaction_messages
+
+// ANCHOR: interactable
+/// A component of the usable objects. Players find it on the objects they look at and show its
+/// prompt.
+#[derive(Visit, Reflect, Debug, Clone)]
+#[visit(optional)]
+pub struct Interaction {
+    prompt: InheritableVariable<String>,
+}
+
+impl Default for Interaction {
+    fn default() -> Self {
+        Self {
+            prompt: "Use".to_string().into(),
+        }
+    }
+}
+
+impl Interaction {
+    pub fn prompt(&self) -> &str {
+        &self.prompt
+    }
+}
+
+/// An extension of the script trait for the usable objects. Such scripts should include the
+/// [`Interaction`] component, subscribe to [`InteractMessage`] and pass the messages to
+/// [`Interactable::on_interaction_message`].
+pub trait Interactable: ScriptTrait {
+    /// Called when someone uses the object.
+    fn interact(&mut self, user: Handle<Node>, ctx: &mut ScriptMessageContext) -> GameResult;
+
+    fn on_interaction_message(
+        &mut self,
+        message: &mut dyn ScriptMessagePayload,
+        ctx: &mut ScriptMessageContext,
+    ) -> GameResult {
+        if let Some(interact) = message.downcast_ref::<InteractMessage>() {
+            self.interact(interact.user, ctx)?;
+        }
+        Ok(())
+    }
+}
+// ANCHOR_END: interactable
+
+// Physics is frozen while the menus are opened, so are the usable objects.
+fn is_world_frozen(ctx: &ScriptContext) -> bool {
+    !*ctx.scene.graph.physics.enabled
+}
+
+fn is_descendant_of(graph: &Graph, mut node: Handle<Node>, ancestor: Handle<Node>) -> bool {
+    while let Ok(node_ref) = graph.try_get(node) {
+        if node == ancestor {
+            return true;
+        }
+        node = node_ref.parent();
+    }
+    false
+}
+
+// ANCHOR: interactor
+/// Finds usable objects in front of the player. Players update it every frame with the position
+/// and direction of their camera.
+#[derive(Visit, Reflect, Debug, Clone)]
+#[visit(optional)]
+pub struct Interactor {
+    /// Maximum distance (in meters) to usable objects.
+    distance: InheritableVariable<f32>,
+
+    /// Collision groups of the colliders of usable objects. Colliders, that are not in the groups
+    /// (walls, for example), block the view.
+    layer: InheritableVariable<u32>,
+
+    #[visit(skip)]
+    #[reflect(hidden)]
+    focus: Handle<Node>,
+}
+
+impl Default for Interactor {
+    fn default() -> Self {
+        Self {
+            distance: 2.0.into(),
+            layer: (1 << 14).into(),
+            focus: Handle::NONE,
+        }
+    }
+}
+
+impl Interactor {
+    /// The usable object, that the player is looking at.
+    pub fn focus(&self) -> Handle<Node> {
+        self.focus
+    }
+
+    /// Looks for a usable object along the ray and returns its prompt. `user` is the rigid body of
+    /// the player, its colliders are ignored.
+    pub fn update<'a>(
+        &mut self,
+        user: Handle<Node>,
+        origin: Vector3<f32>,
+        direction: Vector3<f32>,
+        graph: &'a Graph,
+    ) -> Option<&'a str> {
+        self.focus = Handle::NONE;
+
+        let mut intersections = Vec::new();
+        graph.physics.cast_ray(
+            RayCastOptions {
+                ray_origin: Point3::from(origin),
+                ray_direction: direction,
+                max_len: *self.distance,
+                groups: Default::default(),
+                sort_results: true,
+            },
+            &mut intersections,
+        );
+
+        let hit = intersections
+            .into_iter()
+            .find(|hit| !is_descendant_of(graph, hit.collider, user))?;
+        let collider = graph.try_get_of_type::<Collider>(hit.collider).ok()?;
+        if collider.collision_groups().memberships.0 & *self.layer == 0 {
+            return None;
+        }
+
+        // Colliders are usually attached to the node with the script (or to its descendants).
+        let mut node = hit.collider;
+        while let Ok(node_ref) = graph.try_get(node) {
+            if let Some(interaction) = node_ref.try_get_script_component::<Interaction>() {
+                self.focus = node;
+                return Some(interaction.prompt());
+            }
+            node = node_ref.parent();
+        }
+        None
+    }
+
+    /// Uses the object in focus, if any.
+    pub fn interact(&self, user: Handle<Node>, sender: &ScriptMessageSender) {
+        if self.focus.is_some() {
+            sender.send_to_target(self.focus, InteractMessage { user });
+        }
+    }
+}
+// ANCHOR_END: interactor
+
+// ANCHOR: door
+/// A door, that opens and closes when used or switched. It is either animated (if the animation
+/// player is set) or physical: a rigid body on a revolute joint (hinge), that rotates around its Y
+/// axis.
+#[derive(Visit, Reflect, Debug, Clone, TypeUuidProvider, ComponentProvider)]
+#[type_uuid(id = "6e2d9a41-7b3c-4f58-a1e6-0c8b5d2f9e73")]
+#[visit(optional)]
+pub struct Door {
+    #[component(include)]
+    interaction: Interaction,
+
+    /// An animation player with the opening animation, it is played backwards to close the door.
+    animation_player: InheritableVariable<Handle<AnimationPlayer>>,
+    animation: InheritableVariable<String>,
+
+    /// Angle (in degrees) of the open hinged door.
+    open_angle: InheritableVariable<f32>,
+    /// Maximum angular speed (in degrees per second) of the hinged door.
+    speed: InheritableVariable<f32>,
+
+    #[reflect(hidden)]
+    open: bool,
+
+    /// Rotation of the closed hinged door.
+    #[reflect(hidden)]
+    closed_rotation: Option<UnitQuaternion<f32>>,
+
+    /// The door has neither an animation nor a rigid body, it is checked once on start.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    broken: bool,
+}
+
+impl Default for Door {
+    fn default() -> Self {
+        Self {
+            interaction: Default::default(),
+            animation_player: Default::default(),
+            animation: "Open".to_string().into(),
+            open_angle: 90.0.into(),
+            speed: 120.0.into(),
+            open: false,
+            closed_rotation: None,
+            broken: false,
+        }
+    }
+}
+
+impl Door {
+    fn set_open(&mut self, open: bool, graph: &mut Graph) {
+        self.open = open;
+
+        let Ok(animation_player) = graph.try_get_mut(*self.animation_player) else {
+            return;
+        };
+        if let Some((_, animation)) = animation_player
+            .animations_mut()
+            .get_value_mut_silent()
+            .find_by_name_mut(&*self.animation)
+        {
+            animation
+                .set_loop(false)
+                .set_enabled(true)
+                .set_speed(if open { 1.0 } else { -1.0 });
+        }
+    }
+
+    fn update_hinge(&mut self, handle: Handle<Node>, graph: &mut Graph) -> GameResult {
+        let body = graph.try_get_mut_of_type::<RigidBody>(handle)?;
+        let rotation = body.global_rotation();
+        let closed_rotation = *self.closed_rotation.get_or_insert(rotation);
+
+        // Angle of the door around the hinge relative to the closed state.
+        let angle = (closed_rotation.inverse() * rotation).scaled_axis().y;
+        let target = if self.open {
+            (*self.open_angle).to_radians()
+        } else {
+            0.0
+        };
+        let max_speed = (*self.speed).to_radians();
+        let angular_speed = ((target - angle) * 10.0).clamp(-max_speed, max_speed);
+
+        body.set_ang_vel(closed_rotation * Vector3::y().scale(angular_speed));
+        body.wake_up();
+        Ok(())
+    }
+}
+
+impl Interactable for Door {
+    fn interact(&mut self, _user: Handle<Node>, ctx: &mut ScriptMessageContext) -> GameResult {
+        self.set_open(!self.open, &mut ctx.scene.graph);
+        Ok(())
+    }
+}
+
+impl ScriptTrait for Door {
+    fn on_start(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        ctx.message_dispatcher
+            .subscribe_to::<InteractMessage>(ctx.handle);
+        ctx.message_dispatcher
+            .subscribe_to::<SwitchMessage>(ctx.handle);
+
+        self.broken = self.animation_player.is_none()
+            && ctx
+                .scene
+                .graph
+                .try_get_of_type::<RigidBody>(ctx.handle)
+                .is_err();
+        if self.broken {
+            Log::warn(format!(
+                "The door {} has no animation player and is not a rigid body, it cannot move.",
+                ctx.scene.graph[ctx.handle].name()
+            ));
+        }
+        Ok(())
+    }
+
+    fn on_message(
+        &mut self,
+        message: &mut dyn ScriptMessagePayload,
+        ctx: &mut ScriptMessageContext,
+    ) -> GameResult {
+        if let Some(switch) = message.downcast_ref::<SwitchMessage>() {
+            self.set_open(switch.on, &mut ctx.scene.graph);
+            return Ok(());
+        }
+        self.on_interaction_message(message, ctx)
+    }
+
+    fn on_update(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        if is_world_frozen(ctx) || self.animation_player.is_some() || self.broken {
+            return Ok(());
+        }
+        self.update_hinge(ctx.handle, &mut ctx.scene.graph)
+    }
+}
+// ANCHOR_END: door
+
+// ANCHOR: switch
+/// Sends [`SwitchMessage`] to the linked nodes when used. A toggle switch turns on and off, while a
+/// button always sends `on`.
+#[derive(Visit, Reflect, Debug, Clone, TypeUuidProvider, ComponentProvider)]
+#[type_uuid(id = "a3f81c6d-2e97-4b04-8d5a-9c1e7f3b6d28")]
+#[visit(optional)]
+pub struct Switch {
+    #[component(include)]
+    interaction: Interaction,
+
+    targets: InheritableVariable<Vec<Handle<Node>>>,
+    toggle: InheritableVariable<bool>,
+
+    #[reflect(hidden)]
+    on: bool,
+}
+
+impl Default for Switch {
+    fn default() -> Self {
+        Self {
+            interaction: Default::default(),
+            targets: Default::default(),
+            toggle: true.into(),
+            on: false,
+        }
+    }
+}
+
+impl Interactable for Switch {
+    fn interact(&mut self, user: Handle<Node>, ctx: &mut ScriptMessageContext) -> GameResult {
+        self.on = !*self.toggle || !self.on;
+        for target in self.targets.iter() {
+            ctx.message_sender
+                .send_to_target(*target, SwitchMessage { user, on: self.on });
+        }
+        Ok(())
+    }
+}
+
+impl ScriptTrait for Switch {
+    fn on_start(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        ctx.message_dispatcher
+            .subscribe_to::<InteractMessage>(ctx.handle);
+        Ok(())
+    }
+
+    fn on_message(
+        &mut self,
+        message: &mut dyn ScriptMessagePayload,
+        ctx: &mut ScriptMessageContext,
+    ) -> GameResult {
+        self.on_interaction_message(message, ctx)
+    }
+}
+// ANCHOR_END: switch
+
+// ANCHOR: elevator
+/// A platform, that moves between the floors. It must be a kinematic (velocity based) rigid body,
+/// so the characters standing on it are carried along. Using the elevator sends it to the next
+/// floor, switches send it to the last floor (on) or to the first one (off).
+#[derive(Visit, Reflect, Debug, Clone, TypeUuidProvider, ComponentProvider)]
+#[type_uuid(id = "c9b4e572-18d3-4a6f-b0e2-5f7d3a9c1e84")]
+#[visit(optional)]
+pub struct Elevator {
+    #[component(include)]
+    interaction: Interaction,
+
+    /// Nodes, that mark the positions of the platform on each floor.
+    floors: InheritableVariable<Vec<Handle<Node>>>,
+    /// Meters per second.
+    speed: InheritableVariable<f32>,
+
+    #[reflect(hidden)]
+    floor: usize,
+
+    #[reflect(hidden)]
+    destination: Option<usize>,
+}
+
+impl Default for Elevator {
+    fn default() -> Self {
+        Self {
+            interaction: Default::default(),
+            floors: Default::default(),
+            speed: 2.0.into(),
+            floor: 0,
+            destination: None,
+        }
+    }
+}
+
+impl Elevator {
+    fn go_to(&mut self, floor: usize) {
+        if floor < self.floors.len() && floor != self.floor {
+            self.destination = Some(floor);
+        }
+    }
+}
+
+impl Interactable for Elevator {
+    fn interact(&mut self, _user: Handle<Node>, _ctx: &mut ScriptMessageContext) -> GameResult {
+        // A moving elevator ignores the calls.
+        if self.destination.is_none() && !self.floors.is_empty() {
+            self.go_to((self.floor + 1) % self.floors.len());
+        }
+        Ok(())
+    }
+}
+
+impl ScriptTrait for Elevator {
+    fn on_start(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        ctx.message_dispatcher
+            .subscribe_to::<InteractMessage>(ctx.handle);
+        ctx.message_dispatcher
+            .subscribe_to::<SwitchMessage>(ctx.handle);
+        Ok(())
+    }
+
+    fn on_message(
+        &mut self,
+        message: &mut dyn ScriptMessagePayload,
+        ctx: &mut ScriptMessageContext,
+    ) -> GameResult {
+        if let Some(switch) = message.downcast_ref::<SwitchMessage>() {
+            let floor = if switch.on {
+                self.floors.len().saturating_sub(1)
+            } else {
+                0
+            };
+            self.go_to(floor);
+            return Ok(());
+        }
+        self.on_interaction_message(message, ctx)
+    }
+
+    fn on_update(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        if is_world_frozen(ctx) {
+            return Ok(());
+        }
+
+        let Some(destination) = self.destination else {
+            return Ok(());
+        };
+        let Some(floor) = self.floors.get(destination) else {
+            self.destination = None;
+            return Ok(());
+        };
+        let target = ctx.scene.graph.try_get(*floor)?.global_position();
+
+        let body = ctx
+            .scene
+            .graph
+            .try_get_mut_of_type::<RigidBody>(ctx.handle)?;
+        let offset = target - body.global_position();
+        let distance = offset.norm();
+
+        // The last step is shortened, so the platform stops exactly at the floor.
+        let speed = (*self.speed).min(distance / ctx.dt);
+        match offset.try_normalize(f32::EPSILON) {
+            Some(direction) if distance > 0.001 => body.set_lin_vel(direction.scale(speed)),
+            _ => {
+                body.set_lin_vel(Vector3::default());
+                self.floor = destination;
+                self.destination = None;
+            }
+        }
+
+        Ok(())
+    }
+}
+// ANCHOR_END: elevator
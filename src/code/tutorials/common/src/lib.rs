@@ -0,0 +1,7 @@
+//! Code shared by the tutorial games.
+
+pub mod character;
+pub mod flow;
+pub mod input;
+pub mod interaction;
+pub mod resource;
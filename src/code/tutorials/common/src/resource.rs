@@ -0,0 +1,68 @@
+//! Custom resources stored in RON files, so they could be edited by hand as well as in the editor.
+
+use fyrox::{
+    asset::{
+        io::ResourceIo,
+        loader::{BoxedLoaderFuture, LoaderPayload, ResourceLoader},
+        state::LoadError,
+        ResourceData,
+    },
+    core::{uuid::Uuid, TypeUuidProvider},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    error::Error,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+// ANCHOR: ron_loader
+/// Loads resources of the given type from RON files with the given extensions.
+pub struct RonLoader<T> {
+    extensions: &'static [&'static str],
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> RonLoader<T> {
+    pub fn new(extensions: &'static [&'static str]) -> Self {
+        Self {
+            extensions,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> ResourceLoader for RonLoader<T>
+where
+    T: ResourceData + TypeUuidProvider + DeserializeOwned,
+{
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+
+    fn data_type_uuid(&self) -> Uuid {
+        <T as TypeUuidProvider>::type_uuid()
+    }
+
+    fn load(&self, path: PathBuf, io: Arc<dyn ResourceIo>) -> BoxedLoaderFuture {
+        Box::pin(async move {
+            let content = io.load_file(&path).await.map_err(|err| {
+                LoadError::new(format!("Unable to read {}: {err}", path.display()))
+            })?;
+            let data = ron::de::from_bytes::<T>(&content).map_err(|err| {
+                LoadError::new(format!("Unable to parse {}: {err}", path.display()))
+            })?;
+            Ok(LoaderPayload::new(data))
+        })
+    }
+}
+// ANCHOR_END: ron_loader
+
+/// Saves the data in the format, that is understood by [`RonLoader`]. Could be used in
+/// [`ResourceData::save`].
+pub fn save_ron<T: Serialize>(data: &T, path: &Path) -> Result<(), Box<dyn Error>> {
+    let text = ron::ser::to_string_pretty(data, Default::default())?;
+    std::fs::write(path, text)?;
+    Ok(())
+}
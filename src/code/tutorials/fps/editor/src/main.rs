@@ -1,7 +1,10 @@
 //! Editor with your game connected to it as a plugin.
-use fps::Game;
+use fps::{bot_profile::BotProfile, decal::ImpactTable, sound::SoundTable, Game};
 use fyrox::event_loop::EventLoop;
-use fyroxed_base::{Editor, StartupData};
+use fyroxed_base::{
+    plugins::inspector::editors::resource::ResourceFieldPropertyEditorDefinition, Editor,
+    StartupData,
+};
 
 fn main() {
     let event_loop = EventLoop::new().unwrap();
@@ -10,6 +13,23 @@ fn main() {
         scenes: vec!["data/scene.rgs".into()],
         named_objects: false,
     }));
+    // Sound tables, impact tables and bot profiles are custom resources, the editor needs to know
+    // how to show them in the inspector.
+    editor
+        .property_editors
+        .insert(ResourceFieldPropertyEditorDefinition::<SoundTable>::new(
+            editor.message_sender.clone(),
+        ));
+    editor
+        .property_editors
+        .insert(ResourceFieldPropertyEditorDefinition::<ImpactTable>::new(
+            editor.message_sender.clone(),
+        ));
+    editor
+        .property_editors
+        .insert(ResourceFieldPropertyEditorDefinition::<BotProfile>::new(
+            editor.message_sender.clone(),
+        ));
     editor.add_game_plugin(Game::default());
     editor.run(event_loop)
 }
@@ -1,10 +1,11 @@
 //! Executor with your game connected to it as a plugin.
-use fps::Game;
+use fps::{deathmatch::NetworkMode, Game};
 use fyrox::engine::executor::Executor;
 use fyrox::event_loop::EventLoop;
 
 fn main() {
     let mut executor = Executor::new(EventLoop::new().ok());
-    executor.add_plugin(Game::default());
+    // `--host [address]` starts a deathmatch, `--join [address]` connects to it.
+    executor.add_plugin(Game::default().with_network(NetworkMode::from_args(std::env::args())));
     executor.run()
 }
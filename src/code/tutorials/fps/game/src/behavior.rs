@@ -0,0 +1,336 @@
+use fyrox::{
+    core::{
+        algebra::Vector3, pool::Handle, reflect::prelude::*, type_traits::prelude::*,
+        visitor::prelude::*,
+    },
+    dispatch_behavior_variants,
+    utils::behavior::{
+        leaf, selector, sequence, Behavior, BehaviorNode, BehaviorResult, BehaviorTree, Status,
+    },
+};
+use strum_macros::{AsRefStr, EnumString, VariantNames};
+
+// A point is considered reached, when a bot is closer than this distance to it.
+const REACH_DISTANCE: f32 = 0.5;
+
+// How far a bot runs away from a threat.
+const FLEE_DISTANCE: f32 = 10.0;
+
+// ANCHOR: bot_goal
+// Decision of the behavior tree, the bot script executes it on every frame.
+#[derive(Visit, Default, Debug, Clone, Copy, PartialEq)]
+pub enum BotGoal {
+    #[default]
+    Idle,
+    MoveTo(Vector3<f32>),
+    Attack,
+}
+// ANCHOR_END: bot_goal
+
+// ANCHOR: behavior_context
+pub struct BehaviorContext<'a> {
+    pub position: Vector3<f32>,
+    pub dt: f32,
+    // Health of the bot in 0..1 range.
+    pub health_fraction: f32,
+    pub target_visible: bool,
+    pub close_to_target: bool,
+    pub last_known_target_position: Option<Vector3<f32>>,
+    pub heard_noise: &'a mut Option<Vector3<f32>>,
+    pub patrol_points: &'a [Vector3<f32>],
+    pub patrol_index: &'a mut usize,
+    pub goal: &'a mut BotGoal,
+}
+
+impl BehaviorContext<'_> {
+    fn is_reached(&self, point: Vector3<f32>) -> bool {
+        // Ignore height difference, bots are always on the ground.
+        let mut offset = point - self.position;
+        offset.y = 0.0;
+        offset.norm() <= REACH_DISTANCE
+    }
+}
+// ANCHOR_END: behavior_context
+
+// ANCHOR: conditions
+#[derive(Debug, PartialEq, Default, Visit, Clone)]
+pub struct IsHealthLow {
+    threshold: f32,
+}
+
+impl<'a> Behavior<'a> for IsHealthLow {
+    type Context = BehaviorContext<'a>;
+
+    fn tick(&mut self, context: &mut Self::Context) -> BehaviorResult {
+        if context.health_fraction <= self.threshold {
+            Ok(Status::Success)
+        } else {
+            Ok(Status::Failure)
+        }
+    }
+}
+// ANCHOR_END: conditions
+
+// ANCHOR: actions
+#[derive(Debug, PartialEq, Default, Visit, Clone)]
+pub struct Flee;
+
+impl<'a> Behavior<'a> for Flee {
+    type Context = BehaviorContext<'a>;
+
+    fn tick(&mut self, context: &mut Self::Context) -> BehaviorResult {
+        let Some(threat) = context.last_known_target_position else {
+            return Ok(Status::Failure);
+        };
+
+        let mut away = context.position - threat;
+        away.y = 0.0;
+        let direction = away.try_normalize(f32::EPSILON).unwrap_or_else(Vector3::z);
+        *context.goal = BotGoal::MoveTo(context.position + direction.scale(FLEE_DISTANCE));
+
+        Ok(Status::Running)
+    }
+}
+
+#[derive(Debug, PartialEq, Default, Visit, Clone)]
+pub struct Attack;
+
+impl<'a> Behavior<'a> for Attack {
+    type Context = BehaviorContext<'a>;
+
+    fn tick(&mut self, context: &mut Self::Context) -> BehaviorResult {
+        if !context.close_to_target {
+            return Ok(Status::Failure);
+        }
+
+        *context.goal = BotGoal::Attack;
+
+        Ok(Status::Running)
+    }
+}
+
+#[derive(Debug, PartialEq, Default, Visit, Clone)]
+pub struct Chase;
+
+impl<'a> Behavior<'a> for Chase {
+    type Context = BehaviorContext<'a>;
+
+    fn tick(&mut self, context: &mut Self::Context) -> BehaviorResult {
+        let Some(target_position) = context.last_known_target_position else {
+            return Ok(Status::Failure);
+        };
+
+        // The target is lost and there's nothing at the last known position.
+        if !context.target_visible && context.is_reached(target_position) {
+            return Ok(Status::Failure);
+        }
+
+        *context.goal = BotGoal::MoveTo(target_position);
+
+        Ok(Status::Running)
+    }
+}
+
+#[derive(Debug, PartialEq, Default, Visit, Clone)]
+pub struct Investigate {
+    look_around_time: f32,
+    timer: f32,
+}
+
+impl<'a> Behavior<'a> for Investigate {
+    type Context = BehaviorContext<'a>;
+
+    fn tick(&mut self, context: &mut Self::Context) -> BehaviorResult {
+        let Some(noise_position) = *context.heard_noise else {
+            return Ok(Status::Failure);
+        };
+
+        if !context.is_reached(noise_position) {
+            self.timer = 0.0;
+            *context.goal = BotGoal::MoveTo(noise_position);
+            return Ok(Status::Running);
+        }
+
+        // Stay for a while at the source of the noise, and then forget about it.
+        *context.goal = BotGoal::Idle;
+        self.timer += context.dt;
+        if self.timer >= self.look_around_time {
+            self.timer = 0.0;
+            *context.heard_noise = None;
+            Ok(Status::Success)
+        } else {
+            Ok(Status::Running)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Default, Visit, Clone)]
+pub struct Patrol;
+
+impl<'a> Behavior<'a> for Patrol {
+    type Context = BehaviorContext<'a>;
+
+    fn tick(&mut self, context: &mut Self::Context) -> BehaviorResult {
+        if context.patrol_points.is_empty() {
+            return Ok(Status::Failure);
+        }
+
+        let mut index = *context.patrol_index % context.patrol_points.len();
+        if context.is_reached(context.patrol_points[index]) {
+            index = (index + 1) % context.patrol_points.len();
+        }
+        *context.patrol_index = index;
+
+        *context.goal = BotGoal::MoveTo(context.patrol_points[index]);
+
+        Ok(Status::Running)
+    }
+}
+
+#[derive(Debug, PartialEq, Default, Visit, Clone)]
+pub struct Idle;
+
+impl<'a> Behavior<'a> for Idle {
+    type Context = BehaviorContext<'a>;
+
+    fn tick(&mut self, context: &mut Self::Context) -> BehaviorResult {
+        *context.goal = BotGoal::Idle;
+        Ok(Status::Running)
+    }
+}
+// ANCHOR_END: actions
+
+// ANCHOR: bot_behavior
+#[derive(Debug, PartialEq, Visit, Clone)]
+pub enum BotBehavior {
+    IsHealthLow(IsHealthLow),
+    Flee(Flee),
+    Attack(Attack),
+    Chase(Chase),
+    Investigate(Investigate),
+    Patrol(Patrol),
+    Idle(Idle),
+}
+
+impl Default for BotBehavior {
+    fn default() -> Self {
+        Self::Idle(Default::default())
+    }
+}
+
+dispatch_behavior_variants!(
+    BotBehavior,
+    BehaviorContext<'a>,
+    IsHealthLow,
+    Flee,
+    Attack,
+    Chase,
+    Investigate,
+    Patrol,
+    Idle
+);
+// ANCHOR_END: bot_behavior
+
+// ANCHOR: bot_behavior_variant
+// Every bot prefab could pick its own behavior.
+#[derive(
+    Visit,
+    Reflect,
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    AsRefStr,
+    EnumString,
+    VariantNames,
+    TypeUuidProvider,
+)]
+#[type_uuid(id = "2f4e6d0c-7c43-4b7e-9a0a-3c2d1f5e8b61")]
+pub enum BotBehaviorVariant {
+    // Attacks, chases, investigates noises and patrols, never retreats.
+    #[default]
+    Aggressive,
+    // The same as aggressive, but runs away when the health is low.
+    Cautious,
+    // Stands still until a target shows up, ignores noises.
+    Sentry,
+}
+// ANCHOR_END: bot_behavior_variant
+
+// ANCHOR: tree_building
+type BotBehaviorNode = Handle<BehaviorNode<BotBehavior>>;
+
+fn attack(tree: &mut BehaviorTree<BotBehavior>) -> BotBehaviorNode {
+    leaf(BotBehavior::Attack(Attack), tree)
+}
+
+fn chase(tree: &mut BehaviorTree<BotBehavior>) -> BotBehaviorNode {
+    leaf(BotBehavior::Chase(Chase), tree)
+}
+
+fn investigate(tree: &mut BehaviorTree<BotBehavior>) -> BotBehaviorNode {
+    leaf(
+        BotBehavior::Investigate(Investigate {
+            look_around_time: 3.0,
+            timer: 0.0,
+        }),
+        tree,
+    )
+}
+
+fn patrol(tree: &mut BehaviorTree<BotBehavior>) -> BotBehaviorNode {
+    leaf(BotBehavior::Patrol(Patrol), tree)
+}
+
+fn idle(tree: &mut BehaviorTree<BotBehavior>) -> BotBehaviorNode {
+    leaf(BotBehavior::Idle(Idle), tree)
+}
+
+fn flee_at_low_health(tree: &mut BehaviorTree<BotBehavior>) -> BotBehaviorNode {
+    let is_health_low = leaf(
+        BotBehavior::IsHealthLow(IsHealthLow { threshold: 0.3 }),
+        tree,
+    );
+    let flee = leaf(BotBehavior::Flee(Flee), tree);
+    sequence([is_health_low, flee], tree)
+}
+
+pub fn build_behavior_tree(variant: BotBehaviorVariant) -> BehaviorTree<BotBehavior> {
+    let mut tree = BehaviorTree::new();
+
+    // Selector picks the first behavior that does not fail, so the behaviors are listed in the
+    // order of their priority.
+    let entry = match variant {
+        BotBehaviorVariant::Aggressive => {
+            let members = [
+                attack(&mut tree),
+                chase(&mut tree),
+                investigate(&mut tree),
+                patrol(&mut tree),
+                idle(&mut tree),
+            ];
+            selector(members, &mut tree)
+        }
+        BotBehaviorVariant::Cautious => {
+            let members = [
+                flee_at_low_health(&mut tree),
+                attack(&mut tree),
+                chase(&mut tree),
+                investigate(&mut tree),
+                patrol(&mut tree),
+                idle(&mut tree),
+            ];
+            selector(members, &mut tree)
+        }
+        BotBehaviorVariant::Sentry => {
+            let members = [attack(&mut tree), chase(&mut tree), idle(&mut tree)];
+            selector(members, &mut tree)
+        }
+    };
+
+    tree.set_entry_node(entry);
+
+    tree
+}
+// ANCHOR_END: tree_building
@@ -1,22 +1,46 @@
-use crate::player::Player;
+use crate::{
+    behavior::{build_behavior_tree, BehaviorContext, BotBehavior, BotBehaviorVariant, BotGoal},
+    bot_profile::{profile_of, BotProfile},
+    faction::{Allegiance, Faction},
+    health::{DamageHit, DamageMessage, Health},
+    hud::HudMessage,
+    ragdoll::{self, RagdollSettings},
+    surface::FLESH_SURFACE,
+    Game,
+};
 use fyrox::core::algebra::UnitQuaternion;
+use fyrox::generic_animation::machine::node::AnimationEventCollectionStrategy;
 use fyrox::graph::SceneGraph;
 use fyrox::plugin::error::GameResult;
+use fyrox::utils::{behavior::BehaviorTree, navmesh::NavmeshAgent};
 use fyrox::{
+    asset::Resource,
     core::{
         algebra::{Matrix4, Point3, Vector3},
         math::frustum::Frustum,
         pool::Handle,
+        rand::{thread_rng, Rng},
         reflect::prelude::*,
         type_traits::prelude::*,
         variable::InheritableVariable,
         visitor::prelude::*,
     },
-    scene::{animation::absm::prelude::*, node::Node, rigidbody::RigidBody},
-    script::{ScriptContext, ScriptTrait},
+    scene::{
+        animation::{absm::prelude::*, prelude::*, AnimationPoseExt},
+        collider::InteractionGroups,
+        graph::{physics::RayCastOptions, Graph},
+        navmesh::NavigationalMesh,
+        node::Node,
+        ragdoll::Ragdoll,
+        rigidbody::{RigidBody, RigidBodyType},
+    },
+    script::{
+        ScriptContext, ScriptDeinitContext, ScriptMessageContext, ScriptMessagePayload, ScriptTrait,
+    },
 };
+use std::ops::Range;
 
-#[derive(Visit, Reflect, Default, Debug, Clone, TypeUuidProvider, ComponentProvider)]
+#[derive(Visit, Reflect, Debug, Clone, TypeUuidProvider, ComponentProvider)]
 #[type_uuid(id = "40c225ca-9657-4f4e-af67-48d6482a7aeb")]
 #[visit(optional)]
 pub struct Bot {
@@ -32,11 +56,326 @@ pub struct Bot {
     // ANCHOR_END: absm_model_root_fields
 
     // ANCHOR: target_field
-    #[visit(skip)]
+    // AI memory is saved, so a loaded bot continues to chase its target.
     #[reflect(hidden)]
     target: Handle<Node>,
     // ANCHOR_END: target_field
+
+    // ANCHOR: health_fields
+    #[component(include)]
+    health: Health,
+
+    // Time (in seconds) for which a dead bot stays on the scene.
+    corpse_lifetime: InheritableVariable<f32>,
+
+    #[reflect(hidden)]
+    time_since_death: f32,
+    // ANCHOR_END: health_fields
+
+    // ANCHOR: faction_field
+    #[component(include)]
+    faction: Faction,
+    // ANCHOR_END: faction_field
+
+    // ANCHOR: profile_field
+    // Perception, accuracy, speed and other tuning, that is shared by the bots of the same
+    // difficulty.
+    profile: InheritableVariable<Option<Resource<BotProfile>>>,
+
+    // Time left until the bot reacts on the target, that it has just noticed.
+    #[reflect(hidden)]
+    reaction_timer: f32,
+    // ANCHOR_END: profile_field
+
+    // ANCHOR: melee_fields
+    melee_damage: InheritableVariable<f32>,
+
+    // Horizontal speed (in m/s) that the victim gets on hit.
+    knockback: InheritableVariable<f32>,
+
+    // Minimal time (in seconds) between two successful hits.
+    attack_cooldown: InheritableVariable<f32>,
+
+    #[reflect(hidden)]
+    attack_timer: f32,
+
+    // A flag that tells whether the swing is in its active frames (between `HitBegin` and `HitEnd`
+    // animation signals) and could hurt the target.
+    #[reflect(hidden)]
+    hit_window: bool,
+
+    // Progress (0..1) of the attack animation on the previous frame, it is used only if the
+    // animation has no hit signals.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    attack_progress: f32,
+    // ANCHOR_END: melee_fields
+
+    // ANCHOR: navigation_fields
+    navmesh: InheritableVariable<Handle<NavigationalMesh>>,
+
+    // Distance (in meters) that the target should move away from the end of the current path
+    // to trigger path recalculation.
+    path_recalculation_threshold: InheritableVariable<f32>,
+
+    #[visit(skip)]
+    #[reflect(hidden)]
+    agent: NavmeshAgent,
+
+    // Index of the path point, that the bot is currently walking to.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    path_index: usize,
+
+    // A point to which the current path was built.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    path_destination: Option<Vector3<f32>>,
+
+    #[visit(skip)]
+    #[reflect(hidden)]
+    path_task_pending: bool,
+    // ANCHOR_END: navigation_fields
+
+    // ANCHOR: memory_fields
+    // Time (in seconds) after which the bot forgets the target that it cannot see.
+    forget_timeout: InheritableVariable<f32>,
+
+    #[reflect(hidden)]
+    target_visible: bool,
+
+    #[reflect(hidden)]
+    last_known_target_position: Option<Vector3<f32>>,
+
+    #[reflect(hidden)]
+    time_since_target_seen: f32,
+    // ANCHOR_END: memory_fields
+
+    // ANCHOR: behavior_fields
+    behavior_variant: InheritableVariable<BotBehaviorVariant>,
+
+    // Nodes, that the bot walks between when there's nothing else to do.
+    patrol_points: InheritableVariable<Vec<Handle<Node>>>,
+
+    // The tree is built on start and then stored in the script, so its state survives hot
+    // reloading and saved games.
+    #[reflect(hidden)]
+    behavior: Option<BehaviorTree<BotBehavior>>,
+
+    #[reflect(hidden)]
+    goal: BotGoal,
+
+    #[reflect(hidden)]
+    patrol_index: usize,
+
+    #[reflect(hidden)]
+    heard_noise: Option<Vector3<f32>>,
+    // ANCHOR_END: behavior_fields
+
+    // ANCHOR: ragdoll_fields
+    // The root bone of the skeleton (usually hips), the ragdoll is generated from it on start.
+    skeleton_root: InheritableVariable<Handle<Node>>,
+    ragdoll_settings: InheritableVariable<RagdollSettings>,
+
+    // Time (in seconds) for which a revived bot blends from the pose of the ragdoll back to the
+    // animation.
+    ragdoll_blend_time: InheritableVariable<f32>,
+
+    // The generated ragdoll is a part of the scene, so it is saved as well.
+    #[reflect(hidden)]
+    ragdoll: Handle<Node>,
+
+    // Collision groups of the capsule of the bot, while it is replaced by the ragdoll.
+    #[reflect(hidden)]
+    capsule_groups: Option<InteractionGroups>,
+
+    // The hit, that killed the bot. It is applied to the ragdoll on the next update, when the
+    // body parts are switched to physics.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pending_push: Option<DamageHit>,
+
+    #[visit(skip)]
+    #[reflect(hidden)]
+    blend: Option<RagdollBlend>,
+    // ANCHOR_END: ragdoll_fields
+}
+
+// ANCHOR: ragdoll_blend
+// Local transforms of the bones at the moment of revival, the animation takes over them gradually.
+#[derive(Debug, Clone)]
+struct RagdollBlend {
+    pose: Vec<(Handle<Node>, Vector3<f32>, UnitQuaternion<f32>)>,
+    elapsed: f32,
+}
+// ANCHOR_END: ragdoll_blend
+
+impl Default for Bot {
+    fn default() -> Self {
+        Self {
+            frustum: Default::default(),
+            absm: Default::default(),
+            model_root: Default::default(),
+            target: Default::default(),
+            health: Default::default(),
+            corpse_lifetime: 5.0.into(),
+            time_since_death: 0.0,
+            faction: Default::default(),
+            profile: Default::default(),
+            reaction_timer: 0.0,
+            melee_damage: 20.0.into(),
+            knockback: 4.0.into(),
+            attack_cooldown: 1.0.into(),
+            attack_timer: 0.0,
+            hit_window: false,
+            attack_progress: 0.0,
+            navmesh: Default::default(),
+            path_recalculation_threshold: 1.0.into(),
+            agent: Default::default(),
+            path_index: 0,
+            path_destination: None,
+            path_task_pending: false,
+            forget_timeout: 10.0.into(),
+            target_visible: false,
+            last_known_target_position: None,
+            time_since_target_seen: 0.0,
+            behavior_variant: Default::default(),
+            patrol_points: Default::default(),
+            behavior: None,
+            goal: Default::default(),
+            patrol_index: 0,
+            heard_noise: None,
+            skeleton_root: Default::default(),
+            ragdoll_settings: Default::default(),
+            ragdoll_blend_time: 0.5.into(),
+            ragdoll: Default::default(),
+            capsule_groups: None,
+            pending_push: None,
+            blend: None,
+        }
+    }
+}
+
+// ANCHOR: signal_names
+// Names of the animation signals, that define active frames of the attack animation. These
+// signals should be added to the attack animation in the editor.
+const HIT_BEGIN_SIGNAL: &str = "HitBegin";
+const HIT_END_SIGNAL: &str = "HitEnd";
+
+// Attack animations without the signals hurt in this part of the swing (0 - the beginning of the
+// animation, 1 - the end). The animation is found by the name of the attack state.
+const ATTACK_STATE: &str = "Attack";
+const FALLBACK_HIT_WINDOW: Range<f32> = 0.4..0.6;
+// ANCHOR_END: signal_names
+
+// ANCHOR: unsignaled_attack_progress
+// Returns the progress of the animation of the attack state, if the state is active and the
+// animation has no hit signals.
+fn unsignaled_attack_progress(
+    layer: &MachineLayer,
+    animations: &AnimationContainer,
+) -> Option<f32> {
+    let active_state = layer.active_state();
+    let (_, state) = layer
+        .states()
+        .pair_iter()
+        .find(|(handle, _)| *handle == active_state)?;
+    if state.name != ATTACK_STATE {
+        return None;
+    }
+
+    let (_, pose_node) = layer
+        .nodes()
+        .pair_iter()
+        .find(|(handle, _)| *handle == state.pose_source)?;
+    let PoseNode::PlayAnimation(play_animation) = pose_node else {
+        return None;
+    };
+    let (_, animation) = animations
+        .pair_iter()
+        .find(|(handle, _)| *handle == play_animation.animation)?;
+    if animation
+        .signals()
+        .iter()
+        .any(|signal| signal.name == HIT_BEGIN_SIGNAL)
+    {
+        return None;
+    }
+
+    let time_slice = animation.time_slice();
+    let length = time_slice.end - time_slice.start;
+    (length > 0.0).then(|| (animation.time_position() - time_slice.start) / length)
 }
+// ANCHOR_END: unsignaled_attack_progress
+
+// Height of the bot's eyes relative to its position.
+const HEAD_HEIGHT: f32 = 0.4;
+
+// A path point is considered reached, when the bot is closer than this distance to it.
+const PATH_POINT_REACH_DISTANCE: f32 = 0.5;
+
+// ANCHOR: line_of_sight
+// Checks whether the target could be seen from the given point. Only static geometry (walls,
+// floors, etc.) blocks the view, dynamic objects (such as barrels) are ignored.
+fn is_in_line_of_sight(
+    graph: &Graph,
+    observer: Handle<Node>,
+    eye_position: Vector3<f32>,
+    target: Handle<Node>,
+) -> bool {
+    let Ok(target_node) = graph.try_get(target) else {
+        return false;
+    };
+
+    let ray_direction = target_node.global_position() - eye_position;
+
+    let mut intersections = Vec::new();
+    graph.physics.cast_ray(
+        RayCastOptions {
+            ray_origin: Point3::from(eye_position),
+            ray_direction,
+            max_len: ray_direction.norm(),
+            groups: Default::default(),
+            sort_results: true,
+        },
+        &mut intersections,
+    );
+
+    for intersection in intersections {
+        let Ok(collider) = graph.try_get(intersection.collider) else {
+            continue;
+        };
+
+        let body = collider.parent();
+        if body == target {
+            return true;
+        }
+
+        if body == observer {
+            continue;
+        }
+
+        match graph.try_get_of_type::<RigidBody>(body) {
+            Ok(rigid_body) if rigid_body.body_type() != RigidBodyType::Static => continue,
+            _ => return false,
+        }
+    }
+
+    // Nothing is in the way.
+    true
+}
+// ANCHOR_END: line_of_sight
+
+// ANCHOR: noise_message
+// A loud sound (a shot, an explosion, etc.), bots go check its source if they hear it.
+#[derive(Debug)]
+pub struct NoiseMessage {
+    pub position: Vector3<f32>,
+    // Maximum distance at which the noise could be heard.
+    pub radius: f32,
+}
+impl ScriptMessagePayload for NoiseMessage {}
+// ANCHOR_END: noise_message
 
 impl Bot {
     // ANCHOR: frustum_update
@@ -45,10 +384,10 @@ impl Bot {
         position: Vector3<f32>,
         look_vector: Vector3<f32>,
         up_vector: Vector3<f32>,
-        max_observing_distance: f32,
+        profile: &BotProfile,
     ) {
         // Calculate an average head position.
-        let head_pos = position + Vector3::new(0.0, 0.4, 0.0);
+        let head_pos = position + Vector3::new(0.0, HEAD_HEIGHT, 0.0);
         let look_at = head_pos + look_vector;
 
         // View matrix is constructed using three parameters - observer position, target point,
@@ -61,45 +400,626 @@ impl Bot {
             // Aspect ratio
             16.0 / 9.0,
             // Field of view of the bot
-            90.0f32.to_radians(),
+            profile.field_of_view.to_radians(),
             0.1,
-            max_observing_distance,
+            profile.observing_distance,
         );
         self.frustum =
             Frustum::from_view_projection_matrix(projection_matrix * view_matrix).unwrap();
     }
     // ANCHOR_END: frustum_update
+
+    pub fn health(&self) -> &Health {
+        &self.health
+    }
+
+    pub fn set_navmesh(&mut self, navmesh: Handle<NavigationalMesh>) {
+        self.navmesh.set_value_and_mark_modified(navmesh);
+    }
+
+    // ANCHOR: think
+    fn think(
+        &mut self,
+        position: Vector3<f32>,
+        close_to_target: bool,
+        ctx: &ScriptContext,
+    ) -> GameResult {
+        let patrol_points = self
+            .patrol_points
+            .iter()
+            .filter_map(|point| ctx.scene.graph.try_get(*point).ok())
+            .map(|point| point.global_position())
+            .collect::<Vec<_>>();
+
+        let Some(behavior) = self.behavior.as_mut() else {
+            return Ok(());
+        };
+
+        let mut context = BehaviorContext {
+            position,
+            dt: ctx.dt,
+            health_fraction: self.health.amount() / self.health.max().max(f32::EPSILON),
+            target_visible: self.target_visible,
+            close_to_target,
+            last_known_target_position: self.last_known_target_position,
+            heard_noise: &mut self.heard_noise,
+            patrol_points: &patrol_points,
+            patrol_index: &mut self.patrol_index,
+            goal: &mut self.goal,
+        };
+
+        behavior.tick(&mut context)?;
+
+        Ok(())
+    }
+    // ANCHOR_END: think
+
+    // ANCHOR: forget_target
+    fn forget_target(&mut self) {
+        self.target = Handle::NONE;
+        self.target_visible = false;
+        self.last_known_target_position = None;
+        self.time_since_target_seen = 0.0;
+    }
+    // ANCHOR_END: forget_target
+
+    // ANCHOR: update_perception
+    fn update_perception(
+        &mut self,
+        self_handle: Handle<Node>,
+        head_position: Vector3<f32>,
+        dt: f32,
+        graph: &Graph,
+    ) {
+        if self.target.is_none() {
+            return;
+        }
+
+        let Ok(target) = graph.try_get(self.target) else {
+            // The target does not exist anymore.
+            self.forget_target();
+            return;
+        };
+
+        if target
+            .try_get_script_component::<Health>()
+            .is_some_and(|health| health.is_dead())
+        {
+            self.forget_target();
+            return;
+        }
+
+        let target_position = target.global_position();
+
+        self.target_visible = is_in_line_of_sight(graph, self_handle, head_position, self.target);
+        if self.target_visible {
+            self.last_known_target_position = Some(target_position);
+            self.time_since_target_seen = 0.0;
+        } else {
+            // The target is hidden, remember it for some time, so the bot will search for it.
+            self.time_since_target_seen += dt;
+            if self.time_since_target_seen >= *self.forget_timeout {
+                self.forget_target();
+            }
+        }
+    }
+    // ANCHOR_END: update_perception
+
+    // ANCHOR: request_path
+    fn request_path(
+        &mut self,
+        from: Vector3<f32>,
+        to: Vector3<f32>,
+        ctx: &mut ScriptContext,
+    ) -> GameResult {
+        if self.path_task_pending {
+            return Ok(());
+        }
+
+        let navmesh = ctx.scene.graph.try_get(*self.navmesh)?.navmesh();
+        let mut agent = self.agent.clone();
+        self.path_task_pending = true;
+
+        // Path finding could be quite expensive on large navigational meshes, so it is done in a
+        // separate task to not stall the game.
+        ctx.task_pool.spawn_script_task(
+            ctx.scene_handle,
+            ctx.handle,
+            ctx.script_index,
+            async move {
+                let navmesh = navmesh.read();
+                let found = agent.calculate_path(&navmesh, from, to).is_ok();
+                (agent, found)
+            },
+            move |(agent, found), this: &mut Bot, _ctx| {
+                // The old path is kept if there's no path to the destination, the new one is
+                // requested on the next update.
+                if found {
+                    this.agent = agent;
+                    this.path_index = 0;
+                    this.path_destination = Some(to);
+                }
+                this.path_task_pending = false;
+                Ok(())
+            },
+        );
+
+        Ok(())
+    }
+    // ANCHOR_END: request_path
+
+    // ANCHOR: update_path
+    fn update_path(
+        &mut self,
+        position: Vector3<f32>,
+        target_position: Vector3<f32>,
+        ctx: &mut ScriptContext,
+    ) -> GameResult {
+        let needs_new_path = self.path_destination.map_or(true, |destination| {
+            destination.metric_distance(&target_position) > *self.path_recalculation_threshold
+        });
+
+        if needs_new_path {
+            self.request_path(position, target_position, ctx)?;
+        }
+
+        Ok(())
+    }
+    // ANCHOR_END: update_path
+
+    // ANCHOR: steering_point
+    fn steering_point(&mut self, position: Vector3<f32>) -> Option<Vector3<f32>> {
+        let path = self.agent.path();
+        while let Some(point) = path.get(self.path_index) {
+            // Ignore height difference, the bot is always on the ground.
+            let mut offset = point - position;
+            offset.y = 0.0;
+            if offset.norm() > PATH_POINT_REACH_DISTANCE {
+                return Some(*point);
+            }
+            self.path_index += 1;
+        }
+        None
+    }
+    // ANCHOR_END: steering_point
+
+    // ANCHOR: update_melee_attack
+    fn update_melee_attack(
+        &mut self,
+        close_to_target: bool,
+        ctx: &mut ScriptContext,
+    ) -> GameResult {
+        self.attack_timer -= ctx.dt;
+
+        // Collect the signals of the animations, that are currently playing in the first layer
+        // of the state machine.
+        let absm = ctx.scene.graph.try_get(*self.absm)?;
+        let animation_player = ctx.scene.graph.try_get(absm.animation_player())?;
+        let Some(layer) = absm.machine().layers().first() else {
+            return Ok(());
+        };
+        let events = layer.collect_active_animations_events(
+            absm.machine().parameters(),
+            animation_player.animations(),
+            AnimationEventCollectionStrategy::All,
+        );
+
+        for (_, event) in events.events {
+            if event.name == HIT_BEGIN_SIGNAL {
+                self.hit_window = true;
+            } else if event.name == HIT_END_SIGNAL {
+                self.hit_window = false;
+            }
+        }
+
+        // Without the signals, the window opens and closes when the swing passes its bounds.
+        match unsignaled_attack_progress(layer, animation_player.animations()) {
+            Some(progress) => {
+                if self.attack_progress < FALLBACK_HIT_WINDOW.start
+                    && progress >= FALLBACK_HIT_WINDOW.start
+                {
+                    self.hit_window = true;
+                }
+                if progress >= FALLBACK_HIT_WINDOW.end {
+                    self.hit_window = false;
+                }
+                self.attack_progress = progress;
+            }
+            None => self.attack_progress = 0.0,
+        }
+
+        if !self.hit_window || !close_to_target || self.attack_timer > 0.0 {
+            return Ok(());
+        }
+
+        let self_position = ctx.scene.graph[ctx.handle].global_position();
+        let profile = profile_of(&self.profile);
+
+        // Only one hit per swing is allowed, misses spend the swing as well.
+        self.hit_window = false;
+        self.attack_timer = *self.attack_cooldown / profile.aggression.max(f32::EPSILON);
+        if thread_rng().gen::<f32>() >= profile.accuracy {
+            return Ok(());
+        }
+
+        let target_position = ctx.scene.graph.try_get(self.target)?.global_position();
+        let mut direction = target_position - self_position;
+        direction.y = 0.0;
+        let knockback = direction
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_default()
+            .scale(*self.knockback);
+
+        ctx.message_sender.send_to_target(
+            self.target,
+            DamageMessage {
+                amount: *self.melee_damage,
+                dealer: ctx.handle,
+                knockback,
+                hit: None,
+            },
+        );
+
+        Ok(())
+    }
+    // ANCHOR_END: update_melee_attack
+
+    // ANCHOR: update_dead
+    fn update_dead(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        self.time_since_death += ctx.dt;
+        if self.time_since_death >= *self.corpse_lifetime {
+            // The ragdoll is attached to the bot, so it is removed as well.
+            ctx.scene.graph.remove_node(ctx.handle);
+            return Ok(());
+        }
+
+        if self.is_ragdoll_active(&ctx.scene.graph) {
+            if let Some(hit) = self.pending_push.take() {
+                ragdoll::push_limb(
+                    self.ragdoll,
+                    hit.collider,
+                    hit.position,
+                    hit.impulse,
+                    &mut ctx.scene.graph,
+                );
+            }
+        } else {
+            // Bots without a ragdoll just play the death animation. Switch the state machine to
+            // the death state and keep it there.
+            let state_machine = ctx.scene.graph.try_get_mut(*self.absm)?;
+            state_machine
+                .machine_mut()
+                .get_value_mut_silent()
+                .set_parameter("Run", Parameter::Rule(false))
+                .set_parameter("Attack", Parameter::Rule(false))
+                .set_parameter("Dead", Parameter::Rule(true));
+        }
+
+        // Stop any horizontal movement, but let the body fall.
+        let rigid_body = ctx
+            .scene
+            .graph
+            .try_get_mut_of_type::<RigidBody>(ctx.handle)?;
+        let y_vel = rigid_body.lin_vel().y;
+        rigid_body.set_lin_vel(Vector3::new(0.0, y_vel, 0.0));
+
+        Ok(())
+    }
+    // ANCHOR_END: update_dead
+
+    // ANCHOR: ragdoll_mode
+    fn is_ragdoll_active(&self, graph: &Graph) -> bool {
+        graph
+            .try_get_of_type::<Ragdoll>(self.ragdoll)
+            .is_ok_and(|ragdoll| ragdoll.is_active())
+    }
+
+    // Switches the bot from the animated skeleton to physics. The state machine is disabled,
+    // otherwise the animation would fight with the ragdoll for the bones.
+    fn enable_ragdoll(
+        &mut self,
+        hit: Option<DamageHit>,
+        self_handle: Handle<Node>,
+        graph: &mut Graph,
+    ) {
+        let Ok(ragdoll_node) = graph.try_get_mut_of_type::<Ragdoll>(self.ragdoll) else {
+            return;
+        };
+        ragdoll_node.set_active(true);
+        self.pending_push = hit;
+        self.blend = None;
+
+        if self.capsule_groups.is_none() {
+            self.capsule_groups =
+                ragdoll::set_capsule_groups(self_handle, ragdoll::no_collision_groups(), graph);
+        }
+
+        if let Ok(absm) = graph.try_get_mut(*self.absm) {
+            absm.set_enabled(false);
+        }
+    }
+
+    // Brings a dead bot back to life with the given amount of health. The bones are blended from
+    // the pose of the ragdoll back to the animation.
+    pub fn revive(&mut self, health: f32, self_handle: Handle<Node>, graph: &mut Graph) {
+        if !self.health.is_dead() {
+            return;
+        }
+        self.health.heal(health);
+        self.time_since_death = 0.0;
+        self.pending_push = None;
+
+        if let Some(groups) = self.capsule_groups.take() {
+            ragdoll::set_capsule_groups(self_handle, groups, graph);
+        }
+
+        if let Ok(state_machine) = graph.try_get_mut(*self.absm) {
+            state_machine
+                .machine_mut()
+                .get_value_mut_silent()
+                .set_parameter("Dead", Parameter::Rule(false));
+        }
+
+        let Ok(ragdoll_node) = graph.try_get_mut_of_type::<Ragdoll>(self.ragdoll) else {
+            return;
+        };
+        if !ragdoll_node.is_active() {
+            return;
+        }
+        ragdoll_node.set_active(false);
+        let bones = ragdoll::limbs(ragdoll_node);
+
+        let pose = bones
+            .into_iter()
+            .filter_map(|(bone, _)| {
+                let transform = graph.try_get(bone).ok()?.local_transform();
+                Some((bone, **transform.position(), **transform.rotation()))
+            })
+            .collect();
+        self.blend = Some(RagdollBlend { pose, elapsed: 0.0 });
+    }
+
+    // The state machine stays disabled while blending, so it doesn't apply its pose to the bones.
+    // Instead, the pose is evaluated here, applied, and then mixed with the pose of the ragdoll.
+    fn update_blend(&mut self, dt: f32, graph: &mut Graph) -> GameResult {
+        let Some(blend) = self.blend.as_mut() else {
+            return Ok(());
+        };
+        blend.elapsed += dt;
+        let t = (blend.elapsed / self.ragdoll_blend_time.max(f32::EPSILON)).min(1.0);
+
+        // The machine is taken out of its node for a moment, so it could be evaluated against the
+        // animations of the player without cloning them.
+        let absm = graph.try_get_mut(*self.absm)?;
+        let animation_player = absm.animation_player();
+        let mut machine = std::mem::take(absm.machine_mut().get_value_mut_silent());
+        let animations = graph
+            .try_get_of_type::<AnimationPlayer>(animation_player)
+            .ok()
+            .map(|animation_player| animation_player.animations());
+        if let Some(animations) = animations {
+            machine.evaluate_pose(animations, dt).apply(graph);
+        }
+        *graph
+            .try_get_mut(*self.absm)?
+            .machine_mut()
+            .get_value_mut_silent() = machine;
+
+        for (bone, position, rotation) in blend.pose.iter() {
+            let Ok(bone) = graph.try_get_mut(*bone) else {
+                continue;
+            };
+            let transform = bone.local_transform_mut();
+            let animated_position = **transform.position();
+            let animated_rotation = **transform.rotation();
+            transform
+                .set_position(position.lerp(&animated_position, t))
+                .set_rotation(rotation.slerp(&animated_rotation, t));
+        }
+
+        if t >= 1.0 {
+            self.blend = None;
+            graph.try_get_mut(*self.absm)?.set_enabled(true);
+        }
+
+        Ok(())
+    }
+    // ANCHOR_END: ragdoll_mode
 }
 
 impl ScriptTrait for Bot {
+    // ANCHOR: on_start
+    fn on_start(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        ctx.message_dispatcher
+            .subscribe_to::<DamageMessage>(ctx.handle);
+        ctx.message_dispatcher
+            .subscribe_to::<NoiseMessage>(ctx.handle);
+
+        // The tree could already exist if the bot was loaded from a saved game.
+        if self.behavior.is_none() {
+            self.behavior = Some(build_behavior_tree(*self.behavior_variant));
+        }
+
+        // ANCHOR: faction_registration
+        ctx.plugins
+            .get_mut::<Game>()
+            .factions
+            .register(ctx.handle, self.faction.id());
+        // ANCHOR_END: faction_registration
+
+        // ANCHOR: build_ragdoll
+        // The same goes for the ragdoll, it is a part of the saved scene.
+        if self.skeleton_root.is_some() && ctx.scene.graph.try_get(self.ragdoll).is_err() {
+            self.ragdoll = ragdoll::build_ragdoll(
+                &mut ctx.scene.graph,
+                ctx.handle,
+                *self.skeleton_root,
+                &self.ragdoll_settings,
+            );
+        }
+        // ANCHOR_END: build_ragdoll
+
+        ragdoll::set_capsule_tag(ctx.handle, FLESH_SURFACE, &mut ctx.scene.graph);
+
+        Ok(())
+    }
+    // ANCHOR_END: on_start
+
+    fn on_deinit(&mut self, ctx: &mut ScriptDeinitContext) -> GameResult {
+        ctx.plugins
+            .get_mut::<Game>()
+            .factions
+            .unregister(ctx.node_handle);
+        Ok(())
+    }
+
+    // ANCHOR: on_message
+    fn on_message(
+        &mut self,
+        message: &mut dyn ScriptMessagePayload,
+        ctx: &mut ScriptMessageContext,
+    ) -> GameResult {
+        if let Some(damage) = message.downcast_ref::<DamageMessage>() {
+            // ANCHOR: friendly_fire
+            let factions = &ctx.plugins.get::<Game>().factions;
+            if !factions.can_damage(damage.dealer, ctx.handle) {
+                return Ok(());
+            }
+            let dealer_is_ally = damage.dealer == ctx.handle
+                || factions.allegiance(damage.dealer, ctx.handle) == Allegiance::Friendly;
+            // ANCHOR_END: friendly_fire
+
+            let is_lethal = self.health.take_damage(damage.amount);
+
+            // ANCHOR: ragdoll_on_death
+            if is_lethal {
+                self.enable_ragdoll(damage.hit.clone(), ctx.handle, &mut ctx.scene.graph);
+            }
+            // ANCHOR_END: ragdoll_on_death
+
+            // ANCHOR: hud_notification
+            // Only the hits of the player are shown in the HUD, but every kill goes to the feed.
+            let game = ctx.plugins.get_mut::<Game>();
+            if damage.dealer == game.player {
+                game.hud.send(HudMessage::Hit);
+            }
+            if is_lethal {
+                let killer = ctx
+                    .scene
+                    .graph
+                    .try_get(damage.dealer)
+                    .map(|dealer| dealer.name().to_owned())
+                    .unwrap_or_default();
+                let victim = ctx.scene.graph[ctx.handle].name().to_owned();
+                game.hud.send(HudMessage::Kill { killer, victim });
+            }
+            // ANCHOR_END: hud_notification
+
+            // Go check where the attack came from, even if the attacker wasn't noticed before.
+            // Allies are forgiven, their hits are accidental.
+            if self.target.is_none() && !dealer_is_ally {
+                if let Ok(dealer) = ctx.scene.graph.try_get(damage.dealer) {
+                    self.target = damage.dealer;
+                    self.last_known_target_position = Some(dealer.global_position());
+                    self.time_since_target_seen = 0.0;
+                }
+            }
+        } else if let Some(noise) = message.downcast_ref::<NoiseMessage>() {
+            let position = ctx.scene.graph[ctx.handle].global_position();
+            if position.metric_distance(&noise.position) <= noise.radius {
+                self.heard_noise = Some(noise.position);
+            }
+        }
+        Ok(())
+    }
+    // ANCHOR_END: on_message
+
     fn on_update(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        // Menus are opened or the server simulates the world, it is frozen here.
+        if !ctx.plugins.get::<Game>().is_simulating() {
+            return Ok(());
+        }
+
+        // ANCHOR: dead_check
+        // Dead bots do nothing, except playing the death animation.
+        if self.health.is_dead() {
+            return self.update_dead(ctx);
+        }
+        // ANCHOR_END: dead_check
+
+        // ANCHOR: blend_check
+        // Revived bots stand still until they're back to their animations.
+        if self.blend.is_some() {
+            return self.update_blend(ctx.dt, &mut ctx.scene.graph);
+        }
+        // ANCHOR_END: blend_check
+
         // ANCHOR: frustum_check
-        // Look for targets only if we don't have one.
+        let profile = profile_of(&self.profile);
+        let self_position = ctx.scene.graph[ctx.handle].global_position();
+        let head_position = self_position + Vector3::new(0.0, HEAD_HEIGHT, 0.0);
+
+        // Look for targets only if we don't have one. Only the members of hostile factions are
+        // checked, bots of the same faction are allies.
         if self.target.is_none() {
-            for (handle, node) in ctx.scene.graph.pair_iter() {
-                if node.has_script::<Player>()
+            let factions = &ctx.plugins.get::<Game>().factions;
+            for handle in factions.enemies_of(self.faction.id()) {
+                let Ok(node) = ctx.scene.graph.try_get(handle) else {
+                    continue;
+                };
+                let is_alive = node
+                    .try_get_script_component::<Health>()
+                    .is_some_and(|health| !health.is_dead());
+                if handle != ctx.handle
+                    && is_alive
                     && self.frustum.is_contains_point(node.global_position())
+                    && is_in_line_of_sight(&ctx.scene.graph, ctx.handle, head_position, handle)
                 {
                     self.target = handle;
+                    self.reaction_timer = profile.reaction_time;
                     break;
                 }
             }
         }
 
+        // ANCHOR: perception
+        self.update_perception(ctx.handle, head_position, ctx.dt, &ctx.scene.graph);
+        // ANCHOR_END: perception
+
         // A helper flag, that tells the bot that it is close enough to a target for melee
         // attack.
-        let close_to_target = ctx
-            .scene
-            .graph
-            .try_get(self.target)
-            .map_or(false, |target| {
-                target
-                    .global_position()
-                    .metric_distance(&ctx.scene.graph[ctx.handle].global_position())
-                    < 1.25
-            });
+        let close_to_target = self.target_visible
+            && ctx
+                .scene
+                .graph
+                .try_get(self.target)
+                .map_or(false, |target| {
+                    target.global_position().metric_distance(&self_position)
+                        < profile.melee_distance
+                });
         // ANCHOR_END: frustum_check
 
+        // ANCHOR: decision_making
+        // Let the behavior tree decide what to do and then execute the decision.
+        self.think(self_position, close_to_target, ctx)?;
+
+        // The bot stands still for a moment after noticing a target.
+        let (move_point, attacking) = if self.reaction_timer > 0.0 {
+            self.reaction_timer -= ctx.dt;
+            (None, false)
+        } else {
+            match self.goal {
+                BotGoal::Idle => (None, false),
+                BotGoal::MoveTo(point) => (Some(point), false),
+                BotGoal::Attack => (None, true),
+            }
+        };
+        // ANCHOR_END: decision_making
+
+        // ANCHOR: melee_attack
+        self.update_melee_attack(attacking && close_to_target, ctx)?;
+        // ANCHOR_END: melee_attack
+
         // ANCHOR: root_motion_1
         let model_transform = ctx
             .scene
@@ -114,25 +1034,42 @@ impl ScriptTrait for Bot {
         if let Some(root_motion) = state_machine.machine().pose().root_motion() {
             velocity = model_transform
                 .transform_vector(&root_motion.delta_position)
-                .scale(1.0 / ctx.dt);
+                .scale(profile.movement_speed / ctx.dt);
         }
 
         // ANCHOR: absm_parameters
         state_machine
             .machine_mut()
             .get_value_mut_silent()
-            .set_parameter("Run", Parameter::Rule(self.target.is_some()))
-            .set_parameter("Attack", Parameter::Rule(close_to_target));
+            .set_parameter("Run", Parameter::Rule(move_point.is_some()))
+            .set_parameter("Attack", Parameter::Rule(attacking));
         // ANCHOR_END: absm_parameters
 
         // ANCHOR: root_motion_2
 
         // ANCHOR_END: root_motion_2
 
+        // ANCHOR: navigation
+        // Walk along the path on the navigational mesh if there's one, otherwise go straight to
+        // the destination. Attacking bots just face their target.
+        let mut look_point = if attacking {
+            self.last_known_target_position
+        } else {
+            move_point
+        };
+        if let Some(move_point) = move_point {
+            if self.navmesh.is_some() {
+                self.update_path(self_position, move_point, ctx)?;
+                if let Some(steering_point) = self.steering_point(self_position) {
+                    look_point = Some(steering_point);
+                }
+            }
+        }
+        // ANCHOR_END: navigation
+
         // ANCHOR: angle_calculation
-        let angle_to_target = ctx.scene.graph.try_get(self.target).ok().map(|target| {
-            let self_position = ctx.scene.graph[ctx.handle].global_position();
-            let look_dir = target.global_position() - self_position;
+        let angle_to_target = look_point.map(|look_point| {
+            let look_dir = look_point - self_position;
             look_dir.x.atan2(look_dir.z)
         });
         // ANCHOR_END: angle_calculation
@@ -147,7 +1084,7 @@ impl ScriptTrait for Bot {
         let look_vector = rigid_body.look_vector();
 
         // Update the viewing frustum.
-        self.update_frustum(position, look_vector, up_vector, 20.0);
+        self.update_frustum(position, look_vector, up_vector, &profile);
         // ANCHOR_END: on_update_1
 
         // ANCHOR: rigid_body_velocity
@@ -169,3 +1106,116 @@ impl ScriptTrait for Bot {
         Ok(())
     }
 }
+
+// Bots in a known state for the save test, see `save.rs`.
+#[cfg(test)]
+impl Bot {
+    // A hurt bot, that remembers its target and waits for the next swing.
+    pub(crate) fn mid_fight(target: Handle<Node>) -> Self {
+        let mut bot = Self {
+            target,
+            reaction_timer: 0.25,
+            attack_timer: 0.5,
+            target_visible: true,
+            last_known_target_position: Some(Vector3::new(1.0, 2.0, 3.0)),
+            time_since_target_seen: 1.5,
+            patrol_index: 2,
+            heard_noise: Some(Vector3::new(4.0, 0.0, 4.0)),
+            ..Default::default()
+        };
+        bot.health.take_damage(30.0);
+        bot.behavior = Some(crate::behavior::build_behavior_tree(*bot.behavior_variant));
+        bot
+    }
+
+    // The fields are hidden from the reflection, so they are compared one by one.
+    pub(crate) fn assert_same_runtime_state(&self, other: &Self) {
+        assert_eq!(self.target, other.target);
+        assert_eq!(self.health.amount(), other.health.amount());
+        assert_eq!(self.time_since_death, other.time_since_death);
+        assert_eq!(self.reaction_timer, other.reaction_timer);
+        assert_eq!(self.attack_timer, other.attack_timer);
+        assert_eq!(self.hit_window, other.hit_window);
+        assert_eq!(self.target_visible, other.target_visible);
+        assert_eq!(
+            self.last_known_target_position,
+            other.last_known_target_position
+        );
+        assert_eq!(self.time_since_target_seen, other.time_since_target_seen);
+        assert_eq!(self.behavior.is_some(), other.behavior.is_some());
+        assert_eq!(self.patrol_index, other.patrol_index);
+        assert_eq!(self.heard_noise, other.heard_noise);
+        assert_eq!(self.ragdoll, other.ragdoll);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Bot;
+    use crate::ragdoll;
+    use fyrox::{
+        graph::SceneGraph,
+        scene::{
+            animation::{absm::prelude::*, prelude::*},
+            base::BaseBuilder,
+            collider::{Collider, ColliderBuilder, ColliderShape},
+            graph::Graph,
+            ragdoll::{Ragdoll, RagdollBuilder},
+            rigidbody::RigidBodyBuilder,
+        },
+    };
+
+    // A killed bot lies as a ragdoll without its capsule. Once revived, it gets the capsule back
+    // and blends from the ragdoll to the animation, then the state machine drives the bones again.
+    #[test]
+    fn revive_after_ragdoll() {
+        let mut graph = Graph::new();
+        let animation_player = AnimationPlayerBuilder::new(BaseBuilder::new()).build(&mut graph);
+        let absm = AnimationBlendingStateMachineBuilder::new(BaseBuilder::new())
+            .with_animation_player(animation_player)
+            .build(&mut graph);
+        let capsule = ColliderBuilder::new(BaseBuilder::new())
+            .with_shape(ColliderShape::capsule_y(0.5, 0.3))
+            .build(&mut graph)
+            .to_base();
+        let ragdoll = RagdollBuilder::new(BaseBuilder::new()).build(&mut graph);
+        let bot_node =
+            RigidBodyBuilder::new(BaseBuilder::new().with_child(capsule).with_child(ragdoll))
+                .build(&mut graph)
+                .to_base();
+        let capsule_groups = |graph: &Graph| {
+            graph
+                .try_get_of_type::<Collider>(capsule)
+                .unwrap()
+                .collision_groups()
+        };
+        let groups = capsule_groups(&graph);
+
+        let mut bot = Bot {
+            absm: absm.into(),
+            ragdoll,
+            ..Default::default()
+        };
+        assert!(bot.health.take_damage(bot.health.max()));
+        bot.enable_ragdoll(None, bot_node, &mut graph);
+        assert_eq!(capsule_groups(&graph), ragdoll::no_collision_groups());
+        assert!(!graph.try_get(absm).unwrap().is_enabled());
+
+        bot.revive(50.0, bot_node, &mut graph);
+        assert_eq!(bot.health.amount(), 50.0);
+        assert_eq!(capsule_groups(&graph), groups);
+        assert!(!graph
+            .try_get_of_type::<Ragdoll>(ragdoll)
+            .unwrap()
+            .is_active());
+        assert!(bot.blend.is_some());
+
+        let dt = 0.1;
+        let steps = (*bot.ragdoll_blend_time / dt).ceil() as usize + 1;
+        for _ in 0..steps {
+            bot.update_blend(dt, &mut graph).unwrap();
+        }
+        assert!(bot.blend.is_none());
+        assert!(graph.try_get(absm).unwrap().is_enabled());
+    }
+}
@@ -0,0 +1,88 @@
+use common::resource::{save_ron, RonLoader};
+use fyrox::{
+    asset::{Resource, ResourceData},
+    core::{
+        reflect::prelude::*, type_traits::prelude::*, uuid::Uuid, visitor::prelude::*,
+        TypeUuidProvider,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{error::Error, path::Path};
+
+// ANCHOR: bot_profile
+// Tuning of the bots (difficulty), that is shared between many bots. Profiles are stored in RON
+// files, missing values are taken from the defaults below.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Visit, Reflect, TypeUuidProvider)]
+#[type_uuid(id = "5e2c8b71-9d4a-4f36-b0e3-7a1f6c2d8e94")]
+#[serde(default)]
+pub struct BotProfile {
+    // How far (in meters) the bot sees.
+    pub observing_distance: f32,
+
+    // Horizontal field of view of the bot (in degrees).
+    pub field_of_view: f32,
+
+    // Time (in seconds) for which the bot stands still after noticing a target.
+    pub reaction_time: f32,
+
+    // The bot attacks targets, that are closer than this distance (in meters).
+    pub melee_distance: f32,
+
+    // Chance (0..1) that a swing hurts the target.
+    pub accuracy: f32,
+
+    // Multiplier of the attack rate, aggressive bots swing more often.
+    pub aggression: f32,
+
+    // Multiplier of the speed, that comes from the root motion of the animations.
+    pub movement_speed: f32,
+}
+
+impl Default for BotProfile {
+    fn default() -> Self {
+        Self {
+            observing_distance: 20.0,
+            field_of_view: 90.0,
+            reaction_time: 0.0,
+            melee_distance: 1.25,
+            accuracy: 1.0,
+            aggression: 1.0,
+            movement_speed: 1.0,
+        }
+    }
+}
+
+impl ResourceData for BotProfile {
+    fn type_uuid(&self) -> Uuid {
+        <Self as TypeUuidProvider>::type_uuid()
+    }
+
+    fn save(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        save_ron(self, path)
+    }
+
+    fn can_be_saved(&self) -> bool {
+        true
+    }
+
+    fn try_clone_box(&self) -> Option<Box<dyn ResourceData>> {
+        Some(Box::new(*self))
+    }
+}
+
+pub fn bot_profile_loader() -> RonLoader<BotProfile> {
+    RonLoader::new(&["bot_profile"])
+}
+// ANCHOR_END: bot_profile
+
+// ANCHOR: profile_of
+// Bots read the profile every frame, so the changes in the file (the resource manager reloads it)
+// apply to the running bots immediately. Bots without a profile, or with a profile that is still
+// loading, use the defaults.
+pub fn profile_of(profile: &Option<Resource<BotProfile>>) -> BotProfile {
+    profile
+        .as_ref()
+        .and_then(|profile| profile.data_ref().as_loaded_ref().copied())
+        .unwrap_or_default()
+}
+// ANCHOR_END: profile_of
@@ -0,0 +1,877 @@
+use crate::{
+    player::{Player, PlayerInput},
+    pool::PrefabPools,
+    projectile::Projectile,
+    weapon::Weapon,
+    Game,
+};
+use common::{flow::GameFlow, input::InputMap};
+use fyrox::{
+    core::{
+        algebra::{UnitQuaternion, Vector3},
+        log::Log,
+        net::{NetListener, NetStream},
+        pool::Handle,
+    },
+    fxhash::FxHashMap,
+    graph::SceneGraph,
+    plugin::PluginContext,
+    resource::model::{Model, ModelResource, ModelResourceExtension},
+    scene::{
+        base::SceneNodeId, camera::Camera, graph::Graph, node::Node, rigidbody::RigidBody, Scene,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fmt::{Debug, Formatter},
+    path::PathBuf,
+};
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:10000";
+
+// Every client gets a copy of this prefab.
+const PLAYER_PREFAB: &str = "data/player/player.rgs";
+
+// Dead players appear at a spawn point after this time (in seconds).
+const RESPAWN_TIME: f32 = 3.0;
+
+// ANCHOR: network_mode
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetworkMode {
+    // Runs the match and plays it at the same time (listen server).
+    Host { address: String },
+    // Connects to a running match.
+    Join { address: String },
+}
+
+impl NetworkMode {
+    // Parses `--host [address]` or `--join [address]`, the game is single-player without them.
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Option<Self> {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg != "--host" && arg != "--join" {
+                continue;
+            }
+            let address = args
+                .next()
+                .filter(|address| !address.starts_with("--"))
+                .unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+            return Some(if arg == "--host" {
+                Self::Host { address }
+            } else {
+                Self::Join { address }
+            });
+        }
+        None
+    }
+}
+// ANCHOR_END: network_mode
+
+// ANCHOR: messages
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct InstantiatePrefabMessage {
+    pub path: PathBuf,
+    pub position: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub ids: FxHashMap<Handle<Node>, SceneNodeId>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NodeState {
+    pub node: SceneNodeId,
+    pub position: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+}
+
+// What the HUD of a client shows, clients do not run the scripts of their players.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PlayerStatus {
+    pub health: f32,
+    pub max_health: f32,
+    pub armor: f32,
+    pub max_armor: f32,
+    pub loaded_ammo: u32,
+    pub reserve_ammo: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScoreEntry {
+    pub name: String,
+    pub kills: u32,
+    pub deaths: u32,
+}
+
+// Server messages are meant to be sent to clients.
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ServerMessage {
+    LoadLevel {
+        path: PathBuf,
+    },
+    // `owned` is set only for the client, that controls the player.
+    SpawnPlayer {
+        prefab: InstantiatePrefabMessage,
+        name: String,
+        owned: bool,
+    },
+    RemovePlayer {
+        node: SceneNodeId,
+    },
+    Sync {
+        entity_states: Vec<NodeState>,
+    },
+    Scoreboard {
+        entries: Vec<ScoreEntry>,
+    },
+    // Sent only to the client, that controls the player.
+    PlayerStatus {
+        status: PlayerStatus,
+    },
+    // A weapon fired a hitscan projectile, clients show its trail and impact.
+    Shot {
+        weapon: SceneNodeId,
+        position: Vector3<f32>,
+        rotation: UnitQuaternion<f32>,
+    },
+}
+
+// Client messages are meant to be sent to a server.
+#[derive(Serialize, Deserialize, Debug)]
+pub enum ClientMessage {
+    Input(PlayerInput),
+}
+// ANCHOR_END: messages
+
+fn node_state(node: &Node) -> NodeState {
+    NodeState {
+        node: node.instance_id(),
+        position: **node.local_transform().position(),
+        rotation: **node.local_transform().rotation(),
+    }
+}
+
+fn player_status(node: Handle<Node>, graph: &Graph) -> Option<PlayerStatus> {
+    let player = graph.try_get(node).ok()?.try_get_script::<Player>()?;
+    let weapon = graph
+        .try_get(player.current_weapon())
+        .ok()
+        .and_then(|node| node.try_get_script::<Weapon>());
+    Some(PlayerStatus {
+        health: player.health().amount(),
+        max_health: player.health().max(),
+        armor: player.armor(),
+        max_armor: player.max_armor(),
+        loaded_ammo: weapon.map_or(0, |weapon| weapon.loaded_ammo()),
+        reserve_ammo: weapon.map_or(0, |weapon| weapon.reserve_ammo()),
+    })
+}
+
+// Cameras of the players, that are controlled by someone else, must not render anything.
+fn set_cameras_enabled(graph: &mut Graph, root: Handle<Node>, enabled: bool) {
+    for handle in graph.traverse_handle_iter(root).collect::<Vec<_>>() {
+        if let Ok(camera) = graph.try_get_mut_of_type::<Camera>(handle) {
+            camera.set_enabled(enabled);
+        }
+    }
+}
+
+// ANCHOR: server
+struct Connection {
+    id: usize,
+    stream: NetStream,
+    // The client was told to load the current level and its player is requested.
+    joined: bool,
+    disconnected: bool,
+}
+
+struct ServerPlayer {
+    node: Handle<Node>,
+    // The player of the host has no client, it is a part of the level.
+    client: Option<usize>,
+    prefab: Option<InstantiatePrefabMessage>,
+    score: ScoreEntry,
+    respawn_timer: f32,
+    // The last status, that was sent to the client.
+    status: Option<PlayerStatus>,
+}
+
+pub struct Server {
+    listener: NetListener,
+    connections: Vec<Connection>,
+    players: Vec<ServerPlayer>,
+    prev_node_states: FxHashMap<Handle<Node>, NodeState>,
+    next_client_id: usize,
+    next_spawn_point: usize,
+    scores_changed: bool,
+    level_path: PathBuf,
+}
+
+impl Server {
+    pub fn new(address: &str, level_path: &str) -> std::io::Result<Self> {
+        Ok(Self {
+            listener: NetListener::bind(address)?,
+            connections: Default::default(),
+            players: Default::default(),
+            prev_node_states: Default::default(),
+            next_client_id: 0,
+            next_spawn_point: 0,
+            scores_changed: false,
+            level_path: level_path.into(),
+        })
+    }
+
+    fn send(connection: &mut Connection, message: &ServerMessage) {
+        if connection.stream.send_message(message).is_err() {
+            connection.disconnected = true;
+        }
+    }
+
+    // Only the clients, that loaded the level, receive the messages about it.
+    fn broadcast(&mut self, message: &ServerMessage) {
+        for connection in self.connections.iter_mut().filter(|c| c.joined) {
+            Self::send(connection, message);
+        }
+    }
+
+    fn next_spawn_transform(
+        &mut self,
+        spawn_points: &[Handle<Node>],
+        graph: &Graph,
+    ) -> (Vector3<f32>, UnitQuaternion<f32>) {
+        if spawn_points.is_empty() {
+            return Default::default();
+        }
+        let spawn_point = spawn_points[self.next_spawn_point % spawn_points.len()];
+        self.next_spawn_point += 1;
+        graph
+            .try_get(spawn_point)
+            .map(|node| (node.global_position(), node.global_rotation()))
+            .unwrap_or_default()
+    }
+}
+// ANCHOR_END: server
+
+// ANCHOR: server_join
+impl Server {
+    fn join_new_clients(&mut self, entity_states: Vec<NodeState>, ctx: &mut PluginContext) {
+        let full_sync = ServerMessage::Sync { entity_states };
+        let load_level = ServerMessage::LoadLevel {
+            path: self.level_path.clone(),
+        };
+
+        for connection in self.connections.iter_mut().filter(|c| !c.joined) {
+            Self::send(connection, &load_level);
+            // Players, that were spawned before.
+            for player in self.players.iter() {
+                if let Some(prefab) = player.prefab.clone() {
+                    let message = ServerMessage::SpawnPlayer {
+                        prefab,
+                        name: player.score.name.clone(),
+                        owned: false,
+                    };
+                    Self::send(connection, &message);
+                }
+            }
+            // Delta compression skips everything, that didn't move since the last sync, new
+            // clients need the full state.
+            Self::send(connection, &full_sync);
+            connection.joined = true;
+
+            let client = connection.id;
+            ctx.task_pool.spawn_plugin_task(
+                ctx.resource_manager.request::<Model>(PLAYER_PREFAB),
+                move |result, game: &mut Game, ctx| {
+                    let prefab = result?;
+                    let Some(server) = game
+                        .deathmatch
+                        .as_mut()
+                        .and_then(|deathmatch| deathmatch.server.as_mut())
+                    else {
+                        return Ok(());
+                    };
+                    let Ok(scene) = ctx.scenes.try_get_mut(game.flow.scene()) else {
+                        return Ok(());
+                    };
+                    server.spawn_player(client, prefab, game.waves.spawn_points(), scene);
+                    Ok(())
+                },
+            );
+        }
+    }
+
+    fn spawn_player(
+        &mut self,
+        client: usize,
+        prefab: ModelResource,
+        spawn_points: &[Handle<Node>],
+        scene: &mut Scene,
+    ) {
+        // The client could leave or the level could restart while the prefab was loading.
+        let is_joined = self.connections.iter().any(|c| c.id == client && c.joined);
+        let has_player = self.players.iter().any(|p| p.client == Some(client));
+        if !is_joined || has_player {
+            return;
+        }
+
+        let (position, rotation) = self.next_spawn_transform(spawn_points, &scene.graph);
+        let message = InstantiatePrefabMessage {
+            path: PLAYER_PREFAB.into(),
+            position,
+            rotation,
+            ids: prefab.generate_ids(),
+        };
+        let node = prefab
+            .begin_instantiation(scene)
+            .with_position(position)
+            .with_rotation(rotation)
+            .with_ids(&message.ids)
+            .finish();
+
+        let name = format!("Player {}", client + 1);
+        let graph = &mut scene.graph;
+        graph[node].set_name(&name);
+        if let Some(player) = graph[node].try_get_script_mut::<Player>() {
+            player.remote_input = Some(Default::default());
+        }
+        set_cameras_enabled(graph, node, false);
+
+        for connection in self.connections.iter_mut().filter(|c| c.joined) {
+            let message = ServerMessage::SpawnPlayer {
+                prefab: message.clone(),
+                name: name.clone(),
+                owned: connection.id == client,
+            };
+            Self::send(connection, &message);
+        }
+
+        self.players.push(ServerPlayer {
+            node,
+            client: Some(client),
+            prefab: Some(message),
+            score: ScoreEntry {
+                name,
+                kills: 0,
+                deaths: 0,
+            },
+            respawn_timer: 0.0,
+            status: None,
+        });
+        self.scores_changed = true;
+    }
+}
+// ANCHOR_END: server_join
+
+// ANCHOR: server_update
+impl Server {
+    fn update(
+        &mut self,
+        flow: &GameFlow,
+        host_player: Handle<Node>,
+        spawn_points: &[Handle<Node>],
+        ctx: &mut PluginContext,
+    ) {
+        for stream in self.listener.accept_connections() {
+            self.connections.push(Connection {
+                id: self.next_client_id,
+                stream,
+                joined: false,
+                disconnected: false,
+            });
+            self.next_client_id += 1;
+        }
+
+        let Ok(scene) = ctx.scenes.try_get_mut(flow.scene()) else {
+            return;
+        };
+
+        // The player of the host registers itself on start.
+        if host_player.is_some() && !self.players.iter().any(|p| p.node == host_player) {
+            self.players.push(ServerPlayer {
+                node: host_player,
+                client: None,
+                prefab: None,
+                score: ScoreEntry {
+                    name: "Host".to_string(),
+                    kills: 0,
+                    deaths: 0,
+                },
+                respawn_timer: 0.0,
+                status: None,
+            });
+            self.scores_changed = true;
+        }
+
+        if self.connections.iter().any(|c| !c.joined) {
+            let entity_states = scene
+                .graph
+                .pair_iter()
+                .map(|(_, n)| node_state(n))
+                .collect();
+            self.join_new_clients(entity_states, ctx);
+        }
+        let Ok(scene) = ctx.scenes.try_get_mut(flow.scene()) else {
+            return;
+        };
+
+        // Clients send their input, it is applied by the scripts of their players.
+        for connection in self.connections.iter_mut() {
+            let player = self
+                .players
+                .iter()
+                .find(|p| p.client == Some(connection.id))
+                .map(|p| p.node);
+            connection
+                .stream
+                .process_input::<ClientMessage>(|message| match message {
+                    ClientMessage::Input(input) => {
+                        let remote_input = player
+                            .and_then(|player| scene.graph.try_get_mut(player).ok())
+                            .and_then(|node| node.try_get_script_mut::<Player>())
+                            .and_then(|player| player.remote_input.as_mut());
+                        if let Some(remote_input) = remote_input {
+                            remote_input.merge(input);
+                        }
+                    }
+                });
+        }
+
+        if flow.is_playing() {
+            self.respawn_players(spawn_points, &mut scene.graph, ctx.dt);
+        }
+
+        self.sync_with_delta_compression(&scene.graph);
+        self.send_player_status(&scene.graph);
+
+        if std::mem::take(&mut self.scores_changed) {
+            let entries = self.scores();
+            self.broadcast(&ServerMessage::Scoreboard { entries });
+        }
+
+        self.remove_disconnected(&mut scene.graph);
+    }
+
+    fn respawn_players(&mut self, spawn_points: &[Handle<Node>], graph: &mut Graph, dt: f32) {
+        for i in 0..self.players.len() {
+            let node = self.players[i].node;
+            let is_dead = graph
+                .try_get(node)
+                .ok()
+                .and_then(|node| node.try_get_script::<Player>())
+                .is_some_and(|player| player.health().is_dead());
+            if !is_dead {
+                self.players[i].respawn_timer = 0.0;
+                continue;
+            }
+
+            self.players[i].respawn_timer += dt;
+            if self.players[i].respawn_timer < RESPAWN_TIME {
+                continue;
+            }
+            self.players[i].respawn_timer = 0.0;
+
+            let (position, _) = self.next_spawn_transform(spawn_points, graph);
+            let Ok(body) = graph.try_get_mut_of_type::<RigidBody>(node) else {
+                continue;
+            };
+            body.set_lin_vel(Vector3::default());
+            body.local_transform_mut().set_position(position);
+            if let Some(player) = graph[node].try_get_script_mut::<Player>() {
+                player.respawn();
+            }
+        }
+    }
+
+    fn sync_with_delta_compression(&mut self, graph: &Graph) {
+        let mut entity_states = Vec::new();
+        for (handle, node) in graph.pair_iter() {
+            let current_state = node_state(node);
+
+            // Simple delta compression.
+            let prev_state = self
+                .prev_node_states
+                .entry(handle)
+                .or_insert(current_state.clone());
+
+            if *prev_state != current_state {
+                entity_states.push(current_state.clone());
+                *prev_state = current_state;
+            }
+        }
+
+        if !entity_states.is_empty() {
+            self.broadcast(&ServerMessage::Sync { entity_states });
+        }
+    }
+
+    // Health, armor and ammo are sent to the owners of the players, when they change.
+    fn send_player_status(&mut self, graph: &Graph) {
+        for player in self.players.iter_mut() {
+            let Some(client) = player.client else {
+                continue;
+            };
+            let Some(status) = player_status(player.node, graph) else {
+                continue;
+            };
+            if player.status.as_ref() == Some(&status) {
+                continue;
+            }
+            let connection = self
+                .connections
+                .iter_mut()
+                .find(|c| c.id == client && c.joined);
+            if let Some(connection) = connection {
+                let message = ServerMessage::PlayerStatus {
+                    status: status.clone(),
+                };
+                Self::send(connection, &message);
+                player.status = Some(status);
+            }
+        }
+    }
+
+    fn remove_disconnected(&mut self, graph: &mut Graph) {
+        let disconnected = self
+            .connections
+            .iter()
+            .filter(|c| c.disconnected)
+            .map(|c| c.id)
+            .collect::<Vec<_>>();
+        if disconnected.is_empty() {
+            return;
+        }
+        self.connections.retain(|c| !c.disconnected);
+
+        let (left, stayed) = std::mem::take(&mut self.players)
+            .into_iter()
+            .partition::<Vec<_>, _>(|p| p.client.is_some_and(|id| disconnected.contains(&id)));
+        self.players = stayed;
+        for player in left {
+            if let Ok(node) = graph.try_get(player.node) {
+                let node = node.instance_id();
+                self.broadcast(&ServerMessage::RemovePlayer { node });
+                graph.remove_node(player.node);
+            }
+            Log::info(format!("{} left the match.", player.score.name));
+        }
+        self.scores_changed = true;
+    }
+
+    fn scores(&self) -> Vec<ScoreEntry> {
+        let mut entries = self
+            .players
+            .iter()
+            .map(|p| p.score.clone())
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| b.kills.cmp(&a.kills).then(a.deaths.cmp(&b.deaths)));
+        entries
+    }
+}
+// ANCHOR_END: server_update
+
+// ANCHOR: client
+pub struct Client {
+    connection: NetStream,
+    // Messages, that came while the level is loading, they're applied once it is loaded.
+    pending: VecDeque<ServerMessage>,
+    // The client rotates the camera on its own and sends the angles to the server.
+    yaw: f32,
+    pitch: f32,
+    status: Option<PlayerStatus>,
+}
+
+impl Client {
+    pub fn connect(address: &str) -> std::io::Result<Self> {
+        Ok(Self {
+            connection: NetStream::connect(address)?,
+            pending: Default::default(),
+            yaw: 0.0,
+            pitch: 0.0,
+            status: None,
+        })
+    }
+
+    fn update(
+        &mut self,
+        flow: &mut GameFlow,
+        player: Handle<Node>,
+        input: &InputMap,
+        scores: &mut Vec<ScoreEntry>,
+        pools: &mut PrefabPools,
+        ctx: &mut PluginContext,
+    ) {
+        let pending = &mut self.pending;
+        self.connection
+            .process_input::<ServerMessage>(|message| pending.push_back(message));
+
+        while flow.scene().is_some() || self.is_level_message_next() {
+            let Some(message) = self.pending.pop_front() else {
+                break;
+            };
+            match message {
+                ServerMessage::LoadLevel { path } => {
+                    // Unloads the current level, the rest of the messages wait for the new one.
+                    flow.load_level::<Game>(&path.to_string_lossy(), ctx);
+                }
+                ServerMessage::SpawnPlayer {
+                    prefab,
+                    name,
+                    owned,
+                } => Self::spawn_player(prefab, name, owned, ctx),
+                ServerMessage::RemovePlayer { node } => {
+                    if let Ok(scene) = ctx.scenes.try_get_mut(flow.scene()) {
+                        let handle = scene
+                            .graph
+                            .pair_iter()
+                            .find(|(_, n)| n.instance_id() == node)
+                            .map(|(handle, _)| handle);
+                        if let Some(handle) = handle {
+                            scene.graph.remove_node(handle);
+                        }
+                    }
+                }
+                ServerMessage::Sync { entity_states } => {
+                    if let Ok(scene) = ctx.scenes.try_get_mut(flow.scene()) {
+                        Self::apply_sync(entity_states, &mut scene.graph);
+                    }
+                }
+                ServerMessage::Scoreboard { entries } => *scores = entries,
+                ServerMessage::PlayerStatus { status } => self.status = Some(status),
+                ServerMessage::Shot {
+                    weapon,
+                    position,
+                    rotation,
+                } => {
+                    if let Ok(scene) = ctx.scenes.try_get_mut(flow.scene()) {
+                        Self::show_shot(weapon, position, rotation, pools, scene);
+                    }
+                }
+            }
+        }
+
+        let Ok(scene) = ctx.scenes.try_get_mut(flow.scene()) else {
+            return;
+        };
+
+        // The server simulates the world, everything here just follows it.
+        scene
+            .graph
+            .physics
+            .enabled
+            .set_value_and_mark_modified(false);
+
+        if player.is_none() {
+            return;
+        }
+        let input = if flow.is_playing() {
+            PlayerInput::read(input, ctx.input_state, self.yaw, self.pitch)
+        } else {
+            // Stand still while the menu is opened.
+            PlayerInput {
+                yaw: self.yaw,
+                pitch: self.pitch,
+                ..Default::default()
+            }
+        };
+        self.yaw = input.yaw;
+        self.pitch = input.pitch;
+        if let Err(err) = self.connection.send_message(&ClientMessage::Input(input)) {
+            Log::err(format!("Unable to send the input to the server: {err:?}"));
+        }
+    }
+
+    fn is_level_message_next(&self) -> bool {
+        matches!(self.pending.front(), Some(ServerMessage::LoadLevel { .. }))
+    }
+
+    fn apply_sync(entity_states: Vec<NodeState>, graph: &mut Graph) {
+        let handles = graph
+            .pair_iter()
+            .map(|(handle, node)| (node.instance_id(), handle))
+            .collect::<FxHashMap<_, _>>();
+        for state in entity_states {
+            let Some(node) = handles
+                .get(&state.node)
+                .and_then(|handle| graph.try_get_mut(*handle).ok())
+            else {
+                continue;
+            };
+            node.local_transform_mut()
+                .set_position(state.position)
+                .set_rotation(state.rotation);
+        }
+    }
+
+    // Clients have the same weapons as the server, so the projectile is taken from the prefab of
+    // the weapon. The copy only shows the trail and the impact, the damage is dealt by the server.
+    fn show_shot(
+        weapon: SceneNodeId,
+        position: Vector3<f32>,
+        rotation: UnitQuaternion<f32>,
+        pools: &mut PrefabPools,
+        scene: &mut Scene,
+    ) {
+        let prefab = scene
+            .graph
+            .pair_iter()
+            .find(|(_, n)| n.instance_id() == weapon)
+            .and_then(|(_, n)| n.try_get_script::<Weapon>())
+            .and_then(|weapon| weapon.projectile().cloned());
+        let Some(prefab) = prefab else {
+            return;
+        };
+        let projectile = pools.spawn(&prefab, position, rotation, scene);
+        if let Some(projectile) = scene.graph[projectile].try_get_script_mut::<Projectile>() {
+            projectile.visual_only = true;
+            projectile.reset();
+        }
+    }
+
+    fn spawn_player(
+        desc: InstantiatePrefabMessage,
+        name: String,
+        owned: bool,
+        ctx: &mut PluginContext,
+    ) {
+        ctx.task_pool.spawn_plugin_task(
+            ctx.resource_manager.request::<Model>(&desc.path),
+            move |result, game: &mut Game, ctx| {
+                let prefab = result?;
+                let Ok(scene) = ctx.scenes.try_get_mut(game.flow.scene()) else {
+                    return Ok(());
+                };
+                let node = prefab
+                    .begin_instantiation(scene)
+                    .with_position(desc.position)
+                    .with_rotation(desc.rotation)
+                    .with_ids(&desc.ids)
+                    .finish();
+                let graph = &mut scene.graph;
+                graph[node].set_name(&name);
+
+                if owned {
+                    // Look through the camera of the own player only.
+                    let root = graph.get_root();
+                    set_cameras_enabled(graph, root, false);
+                    set_cameras_enabled(graph, node, true);
+                    game.player = node;
+                } else {
+                    set_cameras_enabled(graph, node, false);
+                }
+                Ok(())
+            },
+        );
+    }
+}
+// ANCHOR_END: client
+
+// ANCHOR: deathmatch
+// A network match, where everyone fights everyone. The server runs the scripts, counts the score
+// and respawns the players, clients send their input and show the state of the server.
+#[derive(Default)]
+pub struct Deathmatch {
+    server: Option<Server>,
+    client: Option<Client>,
+    scores: Vec<ScoreEntry>,
+}
+
+impl Deathmatch {
+    pub fn new(mode: &NetworkMode, level_path: &str) -> std::io::Result<Self> {
+        Ok(match mode {
+            NetworkMode::Host { address } => Self {
+                server: Some(Server::new(address, level_path)?),
+                ..Default::default()
+            },
+            NetworkMode::Join { address } => Self {
+                client: Some(Client::connect(address)?),
+                ..Default::default()
+            },
+        })
+    }
+
+    pub fn is_client(&self) -> bool {
+        self.client.is_some()
+    }
+
+    pub fn scores(&self) -> &[ScoreEntry] {
+        &self.scores
+    }
+
+    // Status of the player of the client, the host shows its own player as usual.
+    pub fn player_status(&self) -> Option<&PlayerStatus> {
+        self.client.as_ref()?.status.as_ref()
+    }
+
+    // Must be called when the level is (re)loaded, everything spawned before is gone.
+    pub fn on_level_loaded(&mut self) {
+        if let Some(server) = self.server.as_mut() {
+            server.players.clear();
+            server.prev_node_states.clear();
+            for connection in server.connections.iter_mut() {
+                connection.joined = false;
+            }
+            server.scores_changed = true;
+        }
+    }
+
+    // Called by the server, when a player is killed by someone (or by themselves).
+    pub fn on_player_killed(&mut self, killer: Handle<Node>, victim: Handle<Node>) {
+        let Some(server) = self.server.as_mut() else {
+            return;
+        };
+        for player in server.players.iter_mut() {
+            if player.node == victim {
+                player.score.deaths += 1;
+            } else if player.node == killer {
+                player.score.kills += 1;
+            }
+        }
+        server.scores_changed = true;
+    }
+
+    // Called by the server, when a weapon fires a hitscan projectile.
+    pub fn on_shot(
+        &mut self,
+        weapon: SceneNodeId,
+        position: Vector3<f32>,
+        rotation: UnitQuaternion<f32>,
+    ) {
+        if let Some(server) = self.server.as_mut() {
+            server.broadcast(&ServerMessage::Shot {
+                weapon,
+                position,
+                rotation,
+            });
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        flow: &mut GameFlow,
+        player: Handle<Node>,
+        spawn_points: &[Handle<Node>],
+        input: &InputMap,
+        pools: &mut PrefabPools,
+        ctx: &mut PluginContext,
+    ) {
+        if let Some(server) = self.server.as_mut() {
+            server.update(flow, player, spawn_points, ctx);
+            self.scores = server.scores();
+        }
+        if let Some(client) = self.client.as_mut() {
+            client.update(flow, player, input, &mut self.scores, pools, ctx);
+        }
+    }
+}
+// ANCHOR_END: deathmatch
+
+impl Clone for Deathmatch {
+    fn clone(&self) -> Self {
+        panic!("non-cloneable!")
+    }
+}
+
+impl Debug for Deathmatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Deathmatch")
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{pool::PrefabPools, surface::surface_of};
+use common::resource::{save_ron, RonLoader};
+use fyrox::{
+    asset::{manager::ResourceManager, ResourceData},
+    core::{
+        algebra::{Matrix4, Point3, UnitQuaternion, Vector3},
+        math,
+        pool::Handle,
+        reflect::prelude::*,
+        type_traits::prelude::*,
+        uuid::Uuid,
+        visitor::prelude::*,
+        TypeUuidProvider,
+    },
+    graph::SceneGraph,
+    resource::{
+        model::Model,
+        texture::{Texture, TextureResource},
+    },
+    scene::{
+        base::BaseBuilder,
+        decal::{Decal, DecalBuilder},
+        graph::physics::Intersection,
+        node::Node,
+        Scene,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+// ANCHOR: impact_table
+// What's left after a hit on a surface.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Visit, Reflect)]
+pub struct SurfaceImpact {
+    // A texture of the hole (or a blood splatter), that is projected on the surface.
+    #[serde(default)]
+    pub decal: Option<PathBuf>,
+
+    // Size of the decal (in meters).
+    #[serde(default = "default_decal_size")]
+    pub decal_size: f32,
+
+    // A prefab with particles (sparks, splinters, blood, etc.).
+    #[serde(default)]
+    pub effect: Option<PathBuf>,
+}
+
+fn default_decal_size() -> f32 {
+    0.1
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Visit, Reflect, TypeUuidProvider)]
+#[type_uuid(id = "b3d9e6a1-7f20-4c5e-8a14-2e6f0c9d5b71")]
+pub struct ImpactTable {
+    // Surface, that is used when the surface of a collider is unknown.
+    pub default_surface: String,
+
+    // Surfaces of colliders without tags, by the names of the colliders.
+    #[serde(default)]
+    pub collider_names: HashMap<String, String>,
+
+    pub surfaces: HashMap<String, SurfaceImpact>,
+}
+
+impl ImpactTable {
+    pub fn impact_on(&self, collider: &Node) -> Option<&SurfaceImpact> {
+        let surface = surface_of(collider, &self.collider_names, &self.default_surface);
+        self.surfaces
+            .get(surface)
+            .or_else(|| self.surfaces.get(&self.default_surface))
+    }
+}
+
+impl ResourceData for ImpactTable {
+    fn type_uuid(&self) -> Uuid {
+        <Self as TypeUuidProvider>::type_uuid()
+    }
+
+    fn save(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        save_ron(self, path)
+    }
+
+    fn can_be_saved(&self) -> bool {
+        true
+    }
+
+    fn try_clone_box(&self) -> Option<Box<dyn ResourceData>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+pub fn impact_table_loader() -> RonLoader<ImpactTable> {
+    RonLoader::new(&["impact_table"])
+}
+// ANCHOR_END: impact_table
+
+// ANCHOR: decal_pool
+// Maximum number of bullet holes on the scene, the oldest ones are reused when the limit is reached.
+pub const MAX_DECALS: usize = 64;
+
+// A ring buffer of decals.
+#[derive(Default, Debug, Clone)]
+pub struct DecalPool {
+    decals: Vec<Handle<Node>>,
+    next: usize,
+}
+
+impl DecalPool {
+    pub fn clear(&mut self) {
+        self.decals.clear();
+        self.next = 0;
+    }
+
+    // Returns a decal, that is either taken from the ring buffer or created, if the buffer is not
+    // full yet (or the old decal was removed together with its parent).
+    fn acquire(&mut self, scene: &mut Scene) -> Handle<Node> {
+        let slot = self.next;
+        self.next = (self.next + 1) % MAX_DECALS;
+
+        if let Some(decal) = self.decals.get(slot) {
+            if scene.graph.try_get_of_type::<Decal>(*decal).is_ok() {
+                return *decal;
+            }
+        }
+
+        let decal = DecalBuilder::new(BaseBuilder::new())
+            .build(&mut scene.graph)
+            .to_base();
+        if slot < self.decals.len() {
+            self.decals[slot] = decal;
+        } else {
+            self.decals.push(decal);
+        }
+        decal
+    }
+
+    // Puts a decal at the given point, oriented by the normal. The decal is attached to the given
+    // node, so it moves together with it.
+    pub fn place(
+        &mut self,
+        texture: TextureResource,
+        size: f32,
+        position: Vector3<f32>,
+        normal: Vector3<f32>,
+        parent: Handle<Node>,
+        scene: &mut Scene,
+    ) {
+        // The decal is a box centered at the hit point, its Z axis is aligned with the normal.
+        let rotation = math::vector_to_quat(normal);
+
+        // The transform of the decal is relative to its parent.
+        let (parent_transform, parent_rotation) = match scene.graph.try_get(parent) {
+            Ok(parent) => (parent.global_transform(), parent.global_rotation()),
+            Err(_) => (Matrix4::identity(), UnitQuaternion::identity()),
+        };
+        let local_position = parent_transform
+            .try_inverse()
+            .unwrap_or_else(Matrix4::identity)
+            .transform_point(&Point3::from(position))
+            .coords;
+        let local_rotation = parent_rotation.inverse() * rotation;
+
+        let decal = self.acquire(scene);
+        scene.graph.link_nodes(decal, parent);
+
+        let Ok(decal) = scene.graph.try_get_mut_of_type::<Decal>(decal) else {
+            return;
+        };
+        decal.set_diffuse_texture(Some(texture));
+        decal
+            .local_transform_mut()
+            .set_position(local_position)
+            .set_rotation(local_rotation)
+            .set_scale(Vector3::new(size, size, size));
+    }
+}
+// ANCHOR_END: decal_pool
+
+// ANCHOR: spawn_impact
+// Places a decal and spawns an effect, that correspond to the surface of the hit collider. Returns
+// `false` if the table has nothing for the surface.
+pub fn spawn_impact(
+    table: &ImpactTable,
+    decals: &mut DecalPool,
+    effects: &mut PrefabPools,
+    intersection: &Intersection,
+    resource_manager: &ResourceManager,
+    scene: &mut Scene,
+) -> bool {
+    let position = intersection.position.coords;
+    let normal = intersection.normal;
+    let Some(impact) = scene
+        .graph
+        .try_get(intersection.collider)
+        .ok()
+        .and_then(|collider| table.impact_on(collider))
+        .cloned()
+    else {
+        return false;
+    };
+
+    if let Some(decal) = impact.decal.as_ref() {
+        decals.place(
+            resource_manager.request::<Texture>(decal),
+            impact.decal_size,
+            position,
+            normal,
+            intersection.collider,
+            scene,
+        );
+    }
+
+    if let Some(effect) = impact.effect.as_ref() {
+        // Resources are cached, so the effect is skipped only until the prefab is loaded.
+        let effect = resource_manager.request::<Model>(effect);
+        if effect.is_ok() {
+            effects.spawn(&effect, position, math::vector_to_quat(normal), scene);
+        }
+    }
+
+    true
+}
+// ANCHOR_END: spawn_impact
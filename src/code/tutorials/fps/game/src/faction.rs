@@ -0,0 +1,180 @@
+use fyrox::{
+    core::{
+        pool::Handle, reflect::prelude::*, type_traits::prelude::*, variable::InheritableVariable,
+        visitor::prelude::*,
+    },
+    scene::node::Node,
+};
+use std::collections::HashMap;
+use strum_macros::{AsRefStr, EnumString, VariantNames};
+
+// ANCHOR: faction_id
+#[derive(
+    Visit,
+    Reflect,
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    AsRefStr,
+    EnumString,
+    VariantNames,
+    TypeUuidProvider,
+)]
+#[type_uuid(id = "b7e3a9d2-4c1f-4a85-8e60-2d9f1b7c3a54")]
+pub enum FactionId {
+    Players,
+    #[default]
+    Monsters,
+    Mercenaries,
+}
+// ANCHOR_END: faction_id
+
+// ANCHOR: allegiance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Allegiance {
+    // Members attack each other on sight.
+    Hostile,
+    // Members ignore each other, unless one of them attacks the other.
+    Neutral,
+    // Members never attack each other.
+    Friendly,
+}
+// ANCHOR_END: allegiance
+
+// ANCHOR: faction
+// A component of the characters (players and bots), that tells which side they're on.
+#[derive(Visit, Reflect, Default, Debug, Clone)]
+#[visit(optional)]
+pub struct Faction {
+    id: InheritableVariable<FactionId>,
+}
+
+impl Faction {
+    pub fn new(id: FactionId) -> Self {
+        Self { id: id.into() }
+    }
+
+    pub fn id(&self) -> FactionId {
+        *self.id
+    }
+}
+// ANCHOR_END: faction
+
+// ANCHOR: allegiance_matrix
+// Relations between the factions. It is symmetric, members of the same faction are always
+// friendly and the relations, that aren't set, are neutral.
+#[derive(Debug, Clone)]
+pub struct AllegianceMatrix {
+    relations: HashMap<(FactionId, FactionId), Allegiance>,
+
+    // Whether the members of friendly factions could hurt each other.
+    pub friendly_fire: bool,
+}
+
+impl Default for AllegianceMatrix {
+    fn default() -> Self {
+        Self {
+            relations: Default::default(),
+            friendly_fire: false,
+        }
+        .with(FactionId::Players, FactionId::Monsters, Allegiance::Hostile)
+        .with(
+            FactionId::Players,
+            FactionId::Mercenaries,
+            Allegiance::Hostile,
+        )
+        .with(
+            FactionId::Monsters,
+            FactionId::Mercenaries,
+            Allegiance::Hostile,
+        )
+    }
+}
+
+impl AllegianceMatrix {
+    pub fn with(mut self, a: FactionId, b: FactionId, allegiance: Allegiance) -> Self {
+        self.set(a, b, allegiance);
+        self
+    }
+
+    pub fn set(&mut self, a: FactionId, b: FactionId, allegiance: Allegiance) {
+        self.relations.insert((a, b), allegiance);
+        self.relations.insert((b, a), allegiance);
+    }
+
+    pub fn allegiance(&self, a: FactionId, b: FactionId) -> Allegiance {
+        if a == b {
+            return Allegiance::Friendly;
+        }
+        self.relations
+            .get(&(a, b))
+            .copied()
+            .unwrap_or(Allegiance::Neutral)
+    }
+}
+// ANCHOR_END: allegiance_matrix
+
+// ANCHOR: faction_registry
+// Characters register themselves here on start, so the bots look for their targets among the
+// members instead of scanning the whole scene graph every frame.
+#[derive(Default, Debug, Clone)]
+pub struct FactionRegistry {
+    members: Vec<(Handle<Node>, FactionId)>,
+    pub allegiances: AllegianceMatrix,
+}
+
+impl FactionRegistry {
+    // Must be called when the scene changes, the members belong to the old scene. The matrix is
+    // a part of the game rules, so it stays.
+    pub fn clear(&mut self) {
+        self.members.clear();
+    }
+
+    pub fn register(&mut self, node: Handle<Node>, faction: FactionId) {
+        match self.members.iter_mut().find(|(member, _)| *member == node) {
+            Some((_, member_faction)) => *member_faction = faction,
+            None => self.members.push((node, faction)),
+        }
+    }
+
+    pub fn unregister(&mut self, node: Handle<Node>) {
+        self.members.retain(|(member, _)| *member != node);
+    }
+
+    pub fn faction_of(&self, node: Handle<Node>) -> Option<FactionId> {
+        self.members
+            .iter()
+            .find(|(member, _)| *member == node)
+            .map(|(_, faction)| *faction)
+    }
+
+    pub fn allegiance(&self, a: Handle<Node>, b: Handle<Node>) -> Allegiance {
+        match (self.faction_of(a), self.faction_of(b)) {
+            (Some(a), Some(b)) => self.allegiances.allegiance(a, b),
+            _ => Allegiance::Neutral,
+        }
+    }
+
+    // Members, that the given faction attacks on sight.
+    pub fn enemies_of(&self, faction: FactionId) -> impl Iterator<Item = Handle<Node>> + '_ {
+        self.members
+            .iter()
+            .filter(move |(_, other)| {
+                self.allegiances.allegiance(faction, *other) == Allegiance::Hostile
+            })
+            .map(|(member, _)| *member)
+    }
+
+    // Checks whether the dealer could hurt the victim, everyone could hurt themselves (with an
+    // explosion, for example).
+    pub fn can_damage(&self, dealer: Handle<Node>, victim: Handle<Node>) -> bool {
+        dealer == victim
+            || self.allegiances.friendly_fire
+            || self.allegiance(dealer, victim) != Allegiance::Friendly
+    }
+}
+// ANCHOR_END: faction_registry
@@ -0,0 +1,81 @@
+use fyrox::{
+    core::{
+        algebra::Vector3, pool::Handle, reflect::prelude::*, variable::InheritableVariable,
+        visitor::prelude::*,
+    },
+    scene::node::Node,
+    script::ScriptMessagePayload,
+};
+
+// ANCHOR: health
+#[derive(Visit, Reflect, Debug, Clone)]
+#[visit(optional)]
+pub struct Health {
+    max: InheritableVariable<f32>,
+
+    // We're storing the amount of taken damage instead of the current health, this way the
+    // maximum health could be tweaked in the editor without touching the actual state.
+    #[reflect(hidden)]
+    damage_taken: f32,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            max: 100.0.into(),
+            damage_taken: 0.0,
+        }
+    }
+}
+
+impl Health {
+    pub fn amount(&self) -> f32 {
+        (*self.max - self.damage_taken).max(0.0)
+    }
+
+    pub fn max(&self) -> f32 {
+        *self.max
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.amount() <= 0.0
+    }
+
+    /// Decreases the health by the given amount. Returns `true` if the damage was lethal, dead
+    /// objects cannot be killed twice.
+    pub fn take_damage(&mut self, amount: f32) -> bool {
+        if self.is_dead() {
+            return false;
+        }
+        self.damage_taken += amount.max(0.0);
+        self.is_dead()
+    }
+
+    pub fn heal(&mut self, amount: f32) {
+        self.damage_taken = (self.damage_taken - amount.max(0.0)).max(0.0);
+    }
+}
+// ANCHOR_END: health
+
+// ANCHOR: damage_message
+#[derive(Debug)]
+pub struct DamageMessage {
+    pub amount: f32,
+    // A node that caused the damage (a player or a bot), could be used to react to the attack.
+    pub dealer: Handle<Node>,
+    // A velocity change, that should be applied to the victim.
+    pub knockback: Vector3<f32>,
+    // Where exactly the victim was hit, if the attack has a point of impact.
+    pub hit: Option<DamageHit>,
+}
+impl ScriptMessagePayload for DamageMessage {}
+
+#[derive(Debug, Clone)]
+pub struct DamageHit {
+    // A collider that was hit, it could be a body part of a ragdoll.
+    pub collider: Handle<Node>,
+    pub position: Vector3<f32>,
+    // A push, that the hit body part gets if the hit turns the victim into a ragdoll.
+    pub impulse: Vector3<f32>,
+}
+// ANCHOR_END: damage_message
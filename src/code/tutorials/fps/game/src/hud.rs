@@ -0,0 +1,365 @@
+use crate::deathmatch::ScoreEntry;
+use fyrox::{
+    core::{
+        algebra::Vector2, color::Color, pool::Handle, reflect::prelude::*, visitor::prelude::*,
+    },
+    graph::SceneGraph,
+    gui::{
+        border::BorderBuilder,
+        brush::Brush,
+        grid::{Column, GridBuilder, Row},
+        progress_bar::{ProgressBarBuilder, ProgressBarMessage},
+        stack_panel::StackPanelBuilder,
+        text::{Text, TextBuilder, TextMessage},
+        widget::{WidgetBuilder, WidgetMessage},
+        BuildContext, HorizontalAlignment, Thickness, UiNode, UserInterface, VerticalAlignment,
+    },
+    plugin::PluginContext,
+};
+use std::{collections::VecDeque, error::Error, path::Path};
+
+// ANCHOR: hud_layout
+// The HUD is made in the UI editor, the code finds the widgets by their names.
+pub const HUD_PATH: &str = "data/ui/hud.ui";
+
+const CROSSHAIR_NAME: &str = "Crosshair";
+const HEALTH_BAR_NAME: &str = "HealthBar";
+const ARMOR_BAR_NAME: &str = "ArmorBar";
+const AMMO_NAME: &str = "Ammo";
+const HIT_MARKER_NAME: &str = "HitMarker";
+const KILL_FEED_NAME: &str = "KillFeed";
+const PROMPT_NAME: &str = "Prompt";
+const SCOREBOARD_NAME: &str = "Scoreboard";
+// ANCHOR_END: hud_layout
+
+// Size of the crosshair (in pixels) without any spread and its growth per each degree of spread.
+const CROSSHAIR_SIZE: f32 = 16.0;
+const CROSSHAIR_SIZE_PER_DEGREE: f32 = 8.0;
+
+// How long (in seconds) the hit marker and kill feed entries stay on the screen.
+const HIT_MARKER_TIME: f32 = 0.15;
+const KILL_FEED_ENTRY_TIME: f32 = 5.0;
+const MAX_KILL_FEED_ENTRIES: usize = 5;
+
+// ANCHOR: hud_message
+// Scripts do not touch the widgets, they send these messages to the HUD instead. The messages are
+// applied once per frame by the plugin.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HudMessage {
+    Health { amount: f32, max: f32 },
+    Armor { amount: f32, max: f32 },
+    Ammo { loaded: u32, reserve: u32 },
+    // Current spread angle of the weapon (in degrees).
+    Spread(f32),
+    // The player hit someone.
+    Hit,
+    Kill { killer: String, victim: String },
+    // What happens if the player uses the object in front of them, hidden if there's nothing.
+    Prompt(Option<String>),
+    // Scores of the network match, hidden if there are none.
+    Scoreboard(Vec<ScoreEntry>),
+}
+// ANCHOR_END: hud_message
+
+// ANCHOR: default_hud
+// A plain HUD with all the widgets, that is used when there's no HUD made in the UI editor.
+pub fn build_default_hud(screen_size: Vector2<f32>) -> UserInterface {
+    let mut ui = UserInterface::new(screen_size);
+    let ctx = &mut ui.build_ctx();
+
+    let crosshair = BorderBuilder::new(
+        WidgetBuilder::new()
+            .with_name(CROSSHAIR_NAME)
+            .with_width(CROSSHAIR_SIZE)
+            .with_height(CROSSHAIR_SIZE)
+            .with_horizontal_alignment(HorizontalAlignment::Center)
+            .with_vertical_alignment(VerticalAlignment::Center)
+            .with_background(Brush::Solid(Color::TRANSPARENT).into())
+            .with_foreground(Brush::Solid(Color::WHITE).into()),
+    )
+    .build(ctx);
+
+    let hit_marker = BorderBuilder::new(
+        WidgetBuilder::new()
+            .with_name(HIT_MARKER_NAME)
+            .with_width(6.0)
+            .with_height(6.0)
+            .with_visibility(false)
+            .with_horizontal_alignment(HorizontalAlignment::Center)
+            .with_vertical_alignment(VerticalAlignment::Center)
+            .with_background(Brush::Solid(Color::RED).into()),
+    )
+    .build(ctx);
+
+    let make_bar = |name: &str, color: Color, ctx: &mut BuildContext| {
+        ProgressBarBuilder::new(
+            WidgetBuilder::new()
+                .with_name(name)
+                .with_width(200.0)
+                .with_height(16.0)
+                .with_margin(Thickness::uniform(2.0))
+                .with_foreground(Brush::Solid(color).into()),
+        )
+        .with_progress(1.0)
+        .build(ctx)
+    };
+    let health_bar = make_bar(HEALTH_BAR_NAME, Color::RED, ctx);
+    let armor_bar = make_bar(ARMOR_BAR_NAME, Color::opaque(80, 140, 255), ctx);
+    let bars = StackPanelBuilder::new(
+        WidgetBuilder::new()
+            .with_margin(Thickness::uniform(16.0))
+            .with_horizontal_alignment(HorizontalAlignment::Left)
+            .with_vertical_alignment(VerticalAlignment::Bottom)
+            .with_child(health_bar)
+            .with_child(armor_bar),
+    )
+    .build(ctx);
+
+    let ammo = TextBuilder::new(
+        WidgetBuilder::new()
+            .with_name(AMMO_NAME)
+            .with_margin(Thickness::uniform(16.0))
+            .with_horizontal_alignment(HorizontalAlignment::Right)
+            .with_vertical_alignment(VerticalAlignment::Bottom),
+    )
+    .build(ctx);
+
+    let kill_feed = StackPanelBuilder::new(
+        WidgetBuilder::new()
+            .with_name(KILL_FEED_NAME)
+            .with_margin(Thickness::uniform(16.0))
+            .with_horizontal_alignment(HorizontalAlignment::Right)
+            .with_vertical_alignment(VerticalAlignment::Top),
+    )
+    .build(ctx);
+
+    let prompt = TextBuilder::new(
+        WidgetBuilder::new()
+            .with_name(PROMPT_NAME)
+            .with_visibility(false)
+            .with_margin(Thickness::top(96.0))
+            .with_horizontal_alignment(HorizontalAlignment::Center)
+            .with_vertical_alignment(VerticalAlignment::Center),
+    )
+    .with_horizontal_text_alignment(HorizontalAlignment::Center)
+    .build(ctx);
+
+    let scoreboard = TextBuilder::new(
+        WidgetBuilder::new()
+            .with_name(SCOREBOARD_NAME)
+            .with_visibility(false)
+            .with_margin(Thickness::uniform(16.0))
+            .with_horizontal_alignment(HorizontalAlignment::Center)
+            .with_vertical_alignment(VerticalAlignment::Top),
+    )
+    .with_horizontal_text_alignment(HorizontalAlignment::Center)
+    .build(ctx);
+
+    GridBuilder::new(
+        WidgetBuilder::new()
+            .with_width(screen_size.x)
+            .with_height(screen_size.y)
+            .with_child(crosshair)
+            .with_child(hit_marker)
+            .with_child(bars)
+            .with_child(ammo)
+            .with_child(kill_feed)
+            .with_child(prompt)
+            .with_child(scoreboard),
+    )
+    .add_row(Row::stretch())
+    .add_column(Column::stretch())
+    .build(ctx);
+
+    ui
+}
+
+// Saves the HUD as the asset, so it could be tweaked in the UI editor.
+pub fn save_hud(ui: &mut UserInterface) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(HUD_PATH);
+    if let Some(folder) = path.parent() {
+        std::fs::create_dir_all(folder)?;
+    }
+    ui.save(path)?;
+    Ok(())
+}
+// ANCHOR_END: default_hud
+
+fn find_widget(ui: &UserInterface, name: &str) -> Handle<UiNode> {
+    ui.find_by_name_from_root(name)
+        .map(|(handle, _)| handle)
+        .unwrap_or_default()
+}
+
+// ANCHOR: hud
+#[derive(Visit, Reflect, Default, Debug, Clone)]
+pub struct Hud {
+    ui: Handle<UserInterface>,
+    crosshair: Handle<UiNode>,
+    health_bar: Handle<UiNode>,
+    armor_bar: Handle<UiNode>,
+    ammo: Handle<UiNode>,
+    hit_marker: Handle<UiNode>,
+    kill_feed: Handle<UiNode>,
+    prompt: Handle<UiNode>,
+    scoreboard: Handle<UiNode>,
+
+    #[visit(skip)]
+    #[reflect(hidden)]
+    messages: Vec<HudMessage>,
+    // The last applied status messages, the widgets are updated only when something changes.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    status: Vec<HudMessage>,
+    visible: bool,
+    hit_marker_timer: f32,
+    #[visit(skip)]
+    #[reflect(hidden)]
+    kill_feed_entries: VecDeque<(Handle<Text>, f32)>,
+}
+
+impl Hud {
+    // Must be called once the HUD is loaded and added to the engine.
+    pub fn attach(&mut self, ui_handle: Handle<UserInterface>, ui: &UserInterface) {
+        *self = Self {
+            ui: ui_handle,
+            crosshair: find_widget(ui, CROSSHAIR_NAME),
+            health_bar: find_widget(ui, HEALTH_BAR_NAME),
+            armor_bar: find_widget(ui, ARMOR_BAR_NAME),
+            ammo: find_widget(ui, AMMO_NAME),
+            hit_marker: find_widget(ui, HIT_MARKER_NAME),
+            kill_feed: find_widget(ui, KILL_FEED_NAME),
+            prompt: find_widget(ui, PROMPT_NAME),
+            scoreboard: find_widget(ui, SCOREBOARD_NAME),
+            visible: true,
+            ..Default::default()
+        };
+    }
+
+    pub fn send(&mut self, message: HudMessage) {
+        self.messages.push(message);
+    }
+
+    fn is_status_changed(&mut self, message: &HudMessage) -> bool {
+        let same_kind = self
+            .status
+            .iter_mut()
+            .find(|status| std::mem::discriminant(*status) == std::mem::discriminant(message));
+        match same_kind {
+            Some(status) if status == message => false,
+            Some(status) => {
+                *status = message.clone();
+                true
+            }
+            None => {
+                self.status.push(message.clone());
+                true
+            }
+        }
+    }
+
+    fn apply(&mut self, message: HudMessage, ui: &mut UserInterface) {
+        match message {
+            HudMessage::Health { amount, max } => {
+                ui.send(
+                    self.health_bar,
+                    ProgressBarMessage::Progress(amount / max.max(f32::EPSILON)),
+                );
+            }
+            HudMessage::Armor { amount, max } => {
+                ui.send(
+                    self.armor_bar,
+                    ProgressBarMessage::Progress(amount / max.max(f32::EPSILON)),
+                );
+            }
+            HudMessage::Ammo { loaded, reserve } => {
+                ui.send(
+                    self.ammo,
+                    TextMessage::Text(format!("{loaded} / {reserve}")),
+                );
+            }
+            HudMessage::Spread(angle) => {
+                let size = CROSSHAIR_SIZE + angle * CROSSHAIR_SIZE_PER_DEGREE;
+                ui.send(self.crosshair, WidgetMessage::Width(size));
+                ui.send(self.crosshair, WidgetMessage::Height(size));
+            }
+            HudMessage::Hit => {
+                self.hit_marker_timer = HIT_MARKER_TIME;
+                ui.send(self.hit_marker, WidgetMessage::Visibility(true));
+            }
+            HudMessage::Kill { killer, victim } => {
+                let entry = TextBuilder::new(WidgetBuilder::new())
+                    .with_text(format!("{killer} killed {victim}"))
+                    .build(&mut ui.build_ctx());
+                ui.send(entry, WidgetMessage::LinkWith(self.kill_feed));
+                self.kill_feed_entries
+                    .push_back((entry, KILL_FEED_ENTRY_TIME));
+
+                if self.kill_feed_entries.len() > MAX_KILL_FEED_ENTRIES {
+                    if let Some((oldest, _)) = self.kill_feed_entries.pop_front() {
+                        ui.send(oldest, WidgetMessage::Remove);
+                    }
+                }
+            }
+            HudMessage::Prompt(prompt) => {
+                ui.send(self.prompt, WidgetMessage::Visibility(prompt.is_some()));
+                if let Some(prompt) = prompt {
+                    ui.send(self.prompt, TextMessage::Text(prompt));
+                }
+            }
+            HudMessage::Scoreboard(entries) => {
+                ui.send(
+                    self.scoreboard,
+                    WidgetMessage::Visibility(!entries.is_empty()),
+                );
+                let text = entries
+                    .iter()
+                    .map(|entry| format!("{}: {} / {}", entry.name, entry.kills, entry.deaths))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                ui.send(self.scoreboard, TextMessage::Text(text));
+            }
+        }
+    }
+
+    pub fn update(&mut self, visible: bool, ctx: &mut PluginContext) {
+        let messages = std::mem::take(&mut self.messages);
+
+        // The HUD could still be loading, the messages are dropped in this case.
+        let Ok(ui) = ctx.user_interfaces.try_get_mut(self.ui) else {
+            return;
+        };
+
+        // Menus are shown without the HUD.
+        if self.visible != visible {
+            self.visible = visible;
+            ui.send(ui.root(), WidgetMessage::Visibility(visible));
+        }
+
+        for message in messages {
+            let is_status = !matches!(message, HudMessage::Hit | HudMessage::Kill { .. });
+            if !is_status || self.is_status_changed(&message) {
+                self.apply(message, ui);
+            }
+        }
+
+        if self.hit_marker_timer > 0.0 {
+            self.hit_marker_timer -= ctx.dt;
+            if self.hit_marker_timer <= 0.0 {
+                ui.send(self.hit_marker, WidgetMessage::Visibility(false));
+            }
+        }
+
+        for (_, time_left) in self.kill_feed_entries.iter_mut() {
+            *time_left -= ctx.dt;
+        }
+        while let Some((entry, time_left)) = self.kill_feed_entries.front() {
+            if *time_left > 0.0 {
+                break;
+            }
+            ui.send(*entry, WidgetMessage::Remove);
+            self.kill_feed_entries.pop_front();
+        }
+    }
+}
+// ANCHOR_END: hud
@@ -1,37 +1,271 @@
 // ANCHOR: player_mod_reg
-use crate::{bot::Bot, player::Player, projectile::Projectile, weapon::Weapon};
+use crate::{
+    behavior::BotBehaviorVariant,
+    bot::Bot,
+    bot_profile::{bot_profile_loader, BotProfile},
+    deathmatch::{Deathmatch, NetworkMode},
+    decal::{impact_table_loader, DecalPool, ImpactTable},
+    faction::{FactionId, FactionRegistry},
+    hud::{build_default_hud, save_hud, Hud, HudMessage, HUD_PATH},
+    lifetime::Lifetime,
+    pickup::{Pickup, PickupEffect},
+    player::Player,
+    pool::PrefabPools,
+    projectile::{Projectile, ProjectileMode},
+    ragdoll::RagdollSettings,
+    save::SAVE_PATH,
+    sound::{sound_table_loader, SoundTable},
+    spawn_point::SpawnPoint,
+    wave::{load_waves, RoundPhase, RoundState, SpawnRequest, WaveManager},
+    weapon::Weapon,
+};
+use common::{
+    flow::{FlowPlugin, GameFlow},
+    input::{AxisBinding, Binding, InputMap},
+    interaction::{Door, Elevator, Switch},
+};
+use fyrox::event::{Event, MouseButton};
+use fyrox::gui::inspector::editors::{
+    enumeration::EnumPropertyEditorDefinition, inherit::InheritablePropertyEditorDefinition,
+    inspectable::InspectablePropertyEditorDefinition, PropertyEditorDefinitionContainer,
+};
+use fyrox::gui::{message::UiMessage, UserInterface};
 use fyrox::plugin::error::GameResult;
-use fyrox::plugin::SceneLoaderResult;
 use fyrox::{
+    asset::Resource,
     core::pool::Handle,
-    core::{reflect::prelude::*, visitor::prelude::*},
+    core::{log::Log, reflect::prelude::*, visitor::prelude::*},
+    keyboard::KeyCode,
     plugin::{Plugin, PluginContext, PluginRegistrationContext},
-    scene::Scene,
+    resource::model::{Model, ModelResourceExtension},
+    scene::node::Node,
 };
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 // Add this line
 pub mod player;
 // ANCHOR_END: player_mod_reg
+pub mod behavior;
 pub mod bot;
+pub mod bot_profile;
+pub mod deathmatch;
+pub mod decal;
+pub mod faction;
+pub mod health;
+pub mod hud;
+pub mod lifetime;
+pub mod pickup;
+pub mod pool;
 pub mod projectile;
+pub mod ragdoll;
+pub mod save;
+pub mod sound;
+pub mod spawn_point;
+pub mod surface;
+pub mod wave;
 pub mod weapon;
 
+// ANCHOR: input_config
+// Player's key bindings, missing actions are taken from the default bindings below.
+const INPUT_CONFIG_PATH: &str = "data/input.ron";
+
+fn default_input_map() -> InputMap {
+    let mut map = InputMap::default()
+        .with_action("move_forward", [Binding::Key(KeyCode::KeyW)])
+        .with_action("move_backward", [Binding::Key(KeyCode::KeyS)])
+        .with_action("move_left", [Binding::Key(KeyCode::KeyA)])
+        .with_action("move_right", [Binding::Key(KeyCode::KeyD)])
+        .with_action("jump", [Binding::Key(KeyCode::Space)])
+        .with_action(
+            "crouch",
+            [
+                Binding::Key(KeyCode::ControlLeft),
+                Binding::Key(KeyCode::KeyC),
+            ],
+        )
+        .with_action("sprint", [Binding::Key(KeyCode::ShiftLeft)])
+        .with_action("shoot", [Binding::Mouse(MouseButton::Left)])
+        .with_action("aim", [Binding::Mouse(MouseButton::Right)])
+        .with_action("reload", [Binding::Key(KeyCode::KeyR)])
+        .with_action("interact", [Binding::Key(KeyCode::KeyE)])
+        .with_action("quick_save", [Binding::Key(KeyCode::F5)])
+        .with_action("quick_load", [Binding::Key(KeyCode::F9)])
+        .with_action("pause", [Binding::Key(KeyCode::Escape)])
+        .with_axis(
+            "look_horizontal",
+            [AxisBinding::MouseX { sensitivity: 0.35 }],
+        )
+        .with_axis("look_vertical", [AxisBinding::MouseY { sensitivity: 0.35 }]);
+
+    let slot_keys = [
+        KeyCode::Digit1,
+        KeyCode::Digit2,
+        KeyCode::Digit3,
+        KeyCode::Digit4,
+        KeyCode::Digit5,
+        KeyCode::Digit6,
+        KeyCode::Digit7,
+        KeyCode::Digit8,
+        KeyCode::Digit9,
+    ];
+    for (i, key) in slot_keys.into_iter().enumerate() {
+        map = map.with_action(&format!("weapon_{}", i + 1), [Binding::Key(key)]);
+    }
+
+    map
+}
+// ANCHOR_END: input_config
+
+// The level, that is loaded when the game starts.
+const LEVEL_PATH: &str = "data/scene.rgs";
+
+// Enemy waves of the round.
+const WAVES_PATH: &str = "data/waves.ron";
+
 #[derive(Visit, Reflect, Default, Debug, Clone)]
 pub struct Game {
-    scene: Handle<Scene>,
+    // Owns the level scene and switches between the menu, the game and the pause.
+    pub flow: GameFlow,
+
+    // The player registers itself here, the game is over when the player dies.
+    pub player: Handle<Node>,
+
+    // Scripts read this to check actions instead of hard-coded keys. It could be modified at runtime
+    // to rebind the actions.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub input: InputMap,
+
+    // ANCHOR: hud_field
+    // Scripts send messages to the HUD, they never access the widgets directly.
+    pub hud: Hud,
+    // ANCHOR_END: hud_field
+
+    // ANCHOR: waves_field
+    // Only the progress of the round is saved, the waves themselves come from the config.
+    #[reflect(hidden)]
+    pub waves: WaveManager,
+    // ANCHOR_END: waves_field
+
+    // ANCHOR: decals_field
+    // Bullet holes of the current level.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub decals: DecalPool,
+    // ANCHOR_END: decals_field
+
+    // ANCHOR: pools_field
+    // Projectiles and effects of the current level.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub pools: PrefabPools,
+    // ANCHOR_END: pools_field
+
+    // ANCHOR: factions_field
+    // Characters of the current level and relations between their factions.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub factions: FactionRegistry,
+    // ANCHOR_END: factions_field
+
+    // ANCHOR: deathmatch_field
+    // Set when the game is started with `--host` or `--join`, see `Game::with_network`.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    network: Option<NetworkMode>,
+
+    // Network match, there are no waves in it.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub deathmatch: Option<Deathmatch>,
+    // ANCHOR_END: deathmatch_field
+
+    // A saved game, that is being loaded.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    loading_save: Option<PathBuf>,
 }
 
 impl Game {
-    fn on_scene_loading_result(
-        &mut self,
-        result: SceneLoaderResult,
-        ctx: &mut PluginContext,
-    ) -> GameResult {
-        if self.scene.is_some() {
-            ctx.scenes.remove(self.scene);
+    // ANCHOR: with_network
+    pub fn with_network(mut self, network: Option<NetworkMode>) -> Self {
+        self.network = network;
+        self
+    }
+
+    // Scripts update the world only on the server (or in a single-player game), clients just show
+    // what the server sends them.
+    pub fn is_simulating(&self) -> bool {
+        self.flow.is_playing() && !self.is_network_client()
+    }
+
+    pub fn is_network_client(&self) -> bool {
+        self.deathmatch
+            .as_ref()
+            .is_some_and(|deathmatch| deathmatch.is_client())
+    }
+    // ANCHOR_END: with_network
+
+    // ANCHOR: round_state
+    pub fn round_state(&self) -> RoundState {
+        self.waves.state()
+    }
+    // ANCHOR_END: round_state
+
+    // ANCHOR: spawn_enemy
+    fn spawn_enemy(&self, request: SpawnRequest, ctx: &mut PluginContext) {
+        ctx.task_pool.spawn_plugin_task(
+            ctx.resource_manager.request::<Model>(&request.prefab),
+            move |result, game: &mut Game, ctx| {
+                // Mark the spawn as finished beforehand, so a broken prefab won't stall the wave.
+                game.waves.finish_spawn();
+
+                let prefab = result?;
+                let Ok(scene) = ctx.scenes.try_get_mut(game.flow.scene()) else {
+                    return Ok(());
+                };
+                let spawn_point = scene.graph.try_get(request.spawn_point)?;
+                let position = spawn_point.global_position();
+                let rotation = spawn_point.global_rotation();
+                let navmesh = spawn_point
+                    .try_get_script::<SpawnPoint>()
+                    .map(|spawn_point| spawn_point.navmesh())
+                    .unwrap_or_default();
+
+                let enemy = prefab.instantiate_at(scene, position, rotation);
+                if let Some(bot) = scene.graph.try_get_mut(enemy)?.try_get_script_mut::<Bot>() {
+                    bot.set_navmesh(navmesh);
+                }
+
+                game.waves.add_enemy(enemy);
+
+                Ok(())
+            },
+        );
+    }
+    // ANCHOR_END: spawn_enemy
+}
+
+impl FlowPlugin for Game {
+    fn flow_mut(&mut self) -> &mut GameFlow {
+        &mut self.flow
+    }
+
+    fn on_level_loaded(&mut self, _ctx: &mut PluginContext) -> GameResult {
+        // Spawn points and the player of the new level will register themselves.
+        self.player = Handle::NONE;
+        self.waves.clear_spawn_points();
+        self.decals.clear();
+        self.pools.clear();
+        self.factions.clear();
+        if let Some(deathmatch) = self.deathmatch.as_mut() {
+            deathmatch.on_level_loaded();
+        }
+        match self.loading_save.take() {
+            Some(path) => self.restore_saved_state(&path)?,
+            None => self.waves.restart(),
         }
-        self.scene = ctx.scenes.add(result?.payload);
         Ok(())
     }
 }
@@ -65,15 +299,245 @@ impl Plugin for Game {
             .script_constructors
             .add::<Bot>("Bot");
         // ANCHOR_END: bot_script_reg
+
+        context
+            .serialization_context
+            .script_constructors
+            .add::<SpawnPoint>("Spawn Point");
+
+        // ANCHOR: lifetime_script_reg
+        context
+            .serialization_context
+            .script_constructors
+            .add::<Lifetime>("Lifetime");
+        // ANCHOR_END: lifetime_script_reg
+
+        // ANCHOR: pickup_script_reg
+        context
+            .serialization_context
+            .script_constructors
+            .add::<Pickup>("Pickup");
+        // ANCHOR_END: pickup_script_reg
+
+        // ANCHOR: interaction_script_reg
+        context
+            .serialization_context
+            .script_constructors
+            .add::<Door>("Door");
+        context
+            .serialization_context
+            .script_constructors
+            .add::<Switch>("Switch");
+        context
+            .serialization_context
+            .script_constructors
+            .add::<Elevator>("Elevator");
+        // ANCHOR_END: interaction_script_reg
+
+        // ANCHOR: table_loaders
+        context
+            .resource_manager
+            .state()
+            .add_loader(sound_table_loader());
+        context
+            .resource_manager
+            .state()
+            .add_loader(impact_table_loader());
+        context
+            .resource_manager
+            .state()
+            .add_loader(bot_profile_loader());
+        // ANCHOR_END: table_loaders
+        Ok(())
+    }
+
+    fn register_property_editors(&self, editors: Arc<PropertyEditorDefinitionContainer>) {
+        editors.insert(EnumPropertyEditorDefinition::<BotBehaviorVariant>::new());
+        editors.insert(InheritablePropertyEditorDefinition::<BotBehaviorVariant>::new());
+        editors.insert(EnumPropertyEditorDefinition::<ProjectileMode>::new());
+        editors.insert(InheritablePropertyEditorDefinition::<ProjectileMode>::new());
+        editors.insert(EnumPropertyEditorDefinition::<PickupEffect>::new());
+        editors.insert(InheritablePropertyEditorDefinition::<PickupEffect>::new());
+        editors.insert(EnumPropertyEditorDefinition::<FactionId>::new());
+        editors.insert(InheritablePropertyEditorDefinition::<FactionId>::new());
+        editors.insert(InspectablePropertyEditorDefinition::<RagdollSettings>::new());
+        editors.insert(InheritablePropertyEditorDefinition::<RagdollSettings>::new());
+        editors.insert(InheritablePropertyEditorDefinition::<
+            Option<Resource<SoundTable>>,
+        >::new());
+        editors.insert(InheritablePropertyEditorDefinition::<
+            Option<Resource<ImpactTable>>,
+        >::new());
+        editors.insert(InheritablePropertyEditorDefinition::<
+            Option<Resource<BotProfile>>,
+        >::new());
+    }
+
+    fn on_loaded(&mut self, _context: PluginContext) -> GameResult {
+        // Input map and waves are not serialized, so they must be reloaded after hot reloading. The
+        // progress of the round is serialized and kept.
+        self.input = InputMap::load_or(INPUT_CONFIG_PATH, default_input_map());
+        self.waves.set_waves(load_waves(WAVES_PATH));
         Ok(())
     }
 
     fn init(&mut self, scene_path: Option<&str>, mut context: PluginContext) -> GameResult {
-        context.load_scene(
-            scene_path.unwrap_or("data/scene.rgs"),
-            false,
-            |result, game: &mut Game, ctx| game.on_scene_loading_result(result, ctx),
-        );
+        self.input = InputMap::load_or(INPUT_CONFIG_PATH, default_input_map());
+        self.waves = WaveManager::new(load_waves(WAVES_PATH));
+
+        // ANCHOR: resource_watcher
+        // The resource manager reloads resources (bot profiles, for example) when their files are
+        // changed, but only if something watches the files. It is needed only during development.
+        #[cfg(debug_assertions)]
+        match fyrox::core::watcher::FileSystemWatcher::new(".", std::time::Duration::from_secs(1)) {
+            Ok(watcher) => context.resource_manager.state().set_watcher(Some(watcher)),
+            Err(err) => Log::warn(format!(
+                "Unable to watch the resources for changes: {err:?}"
+            )),
+        }
+        // ANCHOR_END: resource_watcher
+
+        self.flow = GameFlow::new(LEVEL_PATH);
+
+        // ANCHOR: start_deathmatch
+        if let Some(network) = self.network.as_ref() {
+            match Deathmatch::new(network, LEVEL_PATH) {
+                Ok(deathmatch) => {
+                    // Everyone is in the same faction, but must be able to kill each other.
+                    self.factions.allegiances.friendly_fire = true;
+                    self.deathmatch = Some(deathmatch);
+                }
+                Err(err) => Log::err(format!("Unable to start the deathmatch: {err:?}")),
+            }
+        }
+
+        // Clients wait until the server tells them which level to load.
+        let scene_path = scene_path.filter(|_| !self.is_network_client());
+        self.flow.init::<Game>(scene_path, &mut context);
+        // ANCHOR_END: start_deathmatch
+
+        // ANCHOR: load_hud
+        context.load_ui(HUD_PATH, |result, game: &mut Game, ctx| {
+            let ui = match result {
+                Ok(result) => result.payload,
+                Err(err) => {
+                    Log::warn(format!(
+                        "Unable to load the HUD from {HUD_PATH} ({err:?}), the default one is used."
+                    ));
+                    let mut ui = build_default_hud(ctx.user_interfaces.first().screen_size());
+                    if !Path::new(HUD_PATH).exists() {
+                        if let Err(err) = save_hud(&mut ui) {
+                            Log::warn(format!("Unable to save the HUD to {HUD_PATH} ({err:?})."));
+                        }
+                    }
+                    ui
+                }
+            };
+            let hud = ctx.user_interfaces.add(ui);
+            if let Ok(ui) = ctx.user_interfaces.try_get(hud) {
+                game.hud.attach(hud, ui);
+            }
+            Ok(())
+        });
+        // ANCHOR_END: load_hud
+        Ok(())
+    }
+
+    // ANCHOR: flow_events
+    fn on_os_event(&mut self, event: &Event<()>, mut context: PluginContext) -> GameResult {
+        self.flow
+            .on_os_event(event, self.input.bindings("pause"), &mut context);
+
+        // ANCHOR: quick_save
+        // Network matches cannot be saved.
+        let binding = Binding::from_event(event).filter(|_| self.deathmatch.is_none());
+        if let Some(binding) = binding {
+            let path = Path::new(SAVE_PATH);
+            if self.input.bindings("quick_save").contains(&binding) && self.flow.is_playing() {
+                if let Err(err) = self.save_game(path, &mut context) {
+                    Log::err(format!("Unable to save the game: {err:?}"));
+                }
+            } else if self.input.bindings("quick_load").contains(&binding) && path.exists() {
+                self.load_game(path, &mut context);
+            }
+        }
+        // ANCHOR_END: quick_save
+
+        Ok(())
+    }
+
+    fn on_ui_message(
+        &mut self,
+        context: &mut PluginContext,
+        message: &UiMessage,
+        _ui_handle: Handle<UserInterface>,
+    ) -> GameResult {
+        self.flow.on_ui_message::<Game>(message, context);
+        Ok(())
+    }
+    // ANCHOR_END: flow_events
+
+    // ANCHOR: waves_update
+    fn update(&mut self, context: &mut PluginContext) -> GameResult {
+        // ANCHOR: deathmatch_update
+        if let Some(deathmatch) = self.deathmatch.as_mut() {
+            deathmatch.update(
+                &mut self.flow,
+                self.player,
+                self.waves.spawn_points(),
+                &self.input,
+                &mut self.pools,
+                context,
+            );
+            self.hud
+                .send(HudMessage::Scoreboard(deathmatch.scores().to_vec()));
+            if let Some(status) = deathmatch.player_status() {
+                self.hud.send(HudMessage::Health {
+                    amount: status.health,
+                    max: status.max_health,
+                });
+                self.hud.send(HudMessage::Armor {
+                    amount: status.armor,
+                    max: status.max_armor,
+                });
+                self.hud.send(HudMessage::Ammo {
+                    loaded: status.loaded_ammo,
+                    reserve: status.reserve_ammo,
+                });
+            }
+        }
+        // ANCHOR_END: deathmatch_update
+
+        self.hud.update(self.flow.is_playing(), context);
+
+        // There are no waves and no game over in a deathmatch, dead players respawn.
+        if !self.flow.is_playing() || self.deathmatch.is_some() {
+            return Ok(());
+        }
+
+        let Ok(scene) = context.scenes.try_get(self.flow.scene()) else {
+            return Ok(());
+        };
+
+        let is_player_dead = scene
+            .graph
+            .try_get(self.player)
+            .ok()
+            .and_then(|node| node.try_get_script::<Player>())
+            .is_some_and(|player| player.health().is_dead());
+
+        let requests = self.waves.update(context.dt, &scene.graph);
+        for request in requests {
+            self.spawn_enemy(request, context);
+        }
+
+        if is_player_dead {
+            self.flow.game_over("You Died", context);
+        } else if self.waves.state().phase == RoundPhase::Completed {
+            self.flow.game_over("Victory", context);
+        }
+
         Ok(())
     }
+    // ANCHOR_END: waves_update
 }
@@ -0,0 +1,55 @@
+use crate::Game;
+use fyrox::plugin::error::GameResult;
+use fyrox::{
+    core::{
+        reflect::prelude::*, type_traits::prelude::*, variable::InheritableVariable,
+        visitor::prelude::*,
+    },
+    script::{ScriptContext, ScriptTrait},
+};
+
+// ANCHOR: lifetime
+// Returns the node to its pool (or removes it, if the node is not pooled) after the given time.
+// Add it to the root of short-living prefabs, such as projectile trails or impact effects.
+#[derive(Visit, Reflect, Debug, Clone, TypeUuidProvider, ComponentProvider)]
+#[type_uuid(id = "4c2e8f17-9a3d-4b60-8e5f-1d7a3c9b2e64")]
+#[visit(optional)]
+pub struct Lifetime {
+    // Time (in seconds) the node lives after it was spawned.
+    lifetime: InheritableVariable<f32>,
+
+    #[reflect(hidden)]
+    elapsed: f32,
+}
+
+impl Default for Lifetime {
+    fn default() -> Self {
+        Self {
+            lifetime: 1.0.into(),
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl ScriptTrait for Lifetime {
+    fn on_update(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        // Menus are opened, the world is frozen. Network clients run it as well, to return the
+        // copies of the shots and their impacts back to the pools.
+        if !ctx.plugins.get::<Game>().flow.is_playing() {
+            return Ok(());
+        }
+
+        self.elapsed += ctx.dt;
+        if self.elapsed >= *self.lifetime {
+            // The script stays on the node, so it must be ready for the next use of the node.
+            self.elapsed = 0.0;
+            ctx.plugins
+                .get_mut::<Game>()
+                .pools
+                .despawn(ctx.handle, &mut ctx.scene.graph);
+        }
+
+        Ok(())
+    }
+}
+// ANCHOR_END: lifetime
@@ -0,0 +1,262 @@
+use crate::{
+    player::{ArmorMessage, GiveAmmoMessage, GiveWeaponMessage, HealMessage, Player},
+    sound::play_sound,
+    Game,
+};
+use fyrox::graph::SceneGraph;
+use fyrox::plugin::error::GameResult;
+use fyrox::{
+    core::{
+        algebra::{UnitQuaternion, Vector3},
+        pool::Handle,
+        reflect::prelude::*,
+        type_traits::prelude::*,
+        variable::InheritableVariable,
+        visitor::prelude::*,
+    },
+    resource::model::ModelResource,
+    scene::{collider::Collider, graph::Graph, node::Node, sound::SoundBufferResource},
+    script::{ScriptContext, ScriptTrait},
+};
+use strum_macros::{AsRefStr, EnumString, VariantNames};
+
+// ANCHOR: pickup_effect
+#[derive(Visit, Reflect, Debug, Clone, AsRefStr, EnumString, VariantNames, TypeUuidProvider)]
+#[type_uuid(id = "2f6b9d14-8c3a-4e71-a5d2-7b0e4c9f1a38")]
+pub enum PickupEffect {
+    // Restores the health, the pickup stays in place if the health is full.
+    Health {
+        amount: f32,
+    },
+    // Adds armor, the pickup stays in place if the armor is full.
+    Armor {
+        amount: f32,
+    },
+    // Adds ammo to the weapon, that was made from the prefab.
+    Ammo {
+        weapon: Option<ModelResource>,
+        amount: u32,
+    },
+    // Gives a new weapon or a magazine, if the player already has the weapon.
+    Weapon {
+        prefab: Option<ModelResource>,
+    },
+}
+
+impl Default for PickupEffect {
+    fn default() -> Self {
+        Self::Health { amount: 25.0 }
+    }
+}
+// ANCHOR_END: pickup_effect
+
+// ANCHOR: pickup
+#[derive(Visit, Reflect, Debug, Clone, TypeUuidProvider, ComponentProvider)]
+#[type_uuid(id = "8d1c7a52-3e4f-4b96-9a0d-6f2e8b5c1d47")]
+#[visit(optional)]
+pub struct Pickup {
+    effect: InheritableVariable<PickupEffect>,
+
+    // A collider with the `Is Sensor` flag set, it does not push the player, but detects the
+    // intersections with it.
+    sensor: InheritableVariable<Handle<Collider>>,
+
+    // The pickup appears again after this time (in seconds), if it is set.
+    respawn_time: InheritableVariable<Option<f32>>,
+
+    // A node, that bobs and rotates, usually the model of the pickup.
+    model: InheritableVariable<Handle<Node>>,
+    bob_height: InheritableVariable<f32>,
+    bob_frequency: InheritableVariable<f32>,
+    // Degrees per second.
+    rotation_speed: InheritableVariable<f32>,
+
+    sound: InheritableVariable<Option<SoundBufferResource>>,
+    audio_bus: InheritableVariable<String>,
+
+    // Remaining time until the respawn, the pickup is taken while it is positive.
+    #[reflect(hidden)]
+    respawn_timer: f32,
+
+    #[reflect(hidden)]
+    time: f32,
+
+    // Initial position of the model, the model bobs around it.
+    #[reflect(hidden)]
+    model_origin: Option<Vector3<f32>>,
+}
+
+impl Default for Pickup {
+    fn default() -> Self {
+        Self {
+            effect: Default::default(),
+            sensor: Default::default(),
+            respawn_time: Some(30.0).into(),
+            model: Default::default(),
+            bob_height: 0.1.into(),
+            bob_frequency: 0.5.into(),
+            rotation_speed: 90.0.into(),
+            sound: Default::default(),
+            audio_bus: "Primary".to_string().into(),
+            respawn_timer: 0.0,
+            time: 0.0,
+            model_origin: None,
+        }
+    }
+}
+// ANCHOR_END: pickup
+
+impl Pickup {
+    // ANCHOR: find_player
+    // Returns a player, that touches the sensor.
+    fn find_player(&self, graph: &Graph) -> Option<Handle<Node>> {
+        let sensor = graph.try_get(*self.sensor).ok()?;
+        sensor
+            .intersects(&graph.physics)
+            .filter(|intersection| intersection.has_any_active_contact)
+            .flat_map(|intersection| [intersection.collider1, intersection.collider2])
+            .filter_map(|collider| graph.try_get(collider).ok())
+            .map(|collider| collider.parent())
+            .find(|body| {
+                graph
+                    .try_get(*body)
+                    .ok()
+                    .and_then(|body| body.try_get_script::<Player>())
+                    .is_some_and(|player| !player.health().is_dead())
+            })
+    }
+    // ANCHOR_END: find_player
+
+    // ANCHOR: apply
+    // Returns `false` if the player doesn't need the pickup.
+    fn apply(&self, player: Handle<Node>, ctx: &mut ScriptContext) -> bool {
+        match &*self.effect {
+            PickupEffect::Health { amount } => {
+                let needs_health = ctx
+                    .scene
+                    .graph
+                    .try_get(player)
+                    .ok()
+                    .and_then(|player| player.try_get_script::<Player>())
+                    .is_some_and(|player| player.health().amount() < player.health().max());
+                if needs_health {
+                    ctx.message_sender
+                        .send_to_target(player, HealMessage { amount: *amount });
+                }
+                needs_health
+            }
+            PickupEffect::Armor { amount } => {
+                let needs_armor = ctx
+                    .scene
+                    .graph
+                    .try_get(player)
+                    .ok()
+                    .and_then(|player| player.try_get_script::<Player>())
+                    .is_some_and(|player| player.armor() < player.max_armor());
+                if needs_armor {
+                    ctx.message_sender
+                        .send_to_target(player, ArmorMessage { amount: *amount });
+                }
+                needs_armor
+            }
+            PickupEffect::Ammo { weapon, amount } => {
+                if let Some(weapon) = weapon {
+                    ctx.message_sender.send_to_target(
+                        player,
+                        GiveAmmoMessage {
+                            weapon: weapon.clone(),
+                            amount: *amount,
+                        },
+                    );
+                }
+                true
+            }
+            PickupEffect::Weapon { prefab } => {
+                if let Some(prefab) = prefab {
+                    ctx.message_sender.send_to_target(
+                        player,
+                        GiveWeaponMessage {
+                            weapon: prefab.clone(),
+                        },
+                    );
+                }
+                true
+            }
+        }
+    }
+    // ANCHOR_END: apply
+
+    // ANCHOR: animate
+    // The model is optional, pickups without it just stay still.
+    fn animate(&mut self, dt: f32, graph: &mut Graph) {
+        self.time += dt;
+
+        let Ok(model) = graph.try_get_mut(*self.model) else {
+            return;
+        };
+        let transform = model.local_transform_mut();
+        let origin = *self
+            .model_origin
+            .get_or_insert_with(|| **transform.position());
+
+        let bob =
+            (self.time * *self.bob_frequency * std::f32::consts::TAU).sin() * *self.bob_height;
+        transform
+            .set_position(origin + Vector3::new(0.0, bob, 0.0))
+            .set_rotation(UnitQuaternion::from_axis_angle(
+                &Vector3::y_axis(),
+                (self.time * *self.rotation_speed).to_radians(),
+            ));
+    }
+    // ANCHOR_END: animate
+}
+
+impl ScriptTrait for Pickup {
+    // ANCHOR: on_update
+    fn on_update(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        // Menus are opened or the server simulates the world, it is frozen here.
+        if !ctx.plugins.get::<Game>().is_simulating() {
+            return Ok(());
+        }
+
+        if self.respawn_timer > 0.0 {
+            self.respawn_timer -= ctx.dt;
+            if self.respawn_timer <= 0.0 {
+                ctx.scene.graph[ctx.handle].set_visibility(true);
+            }
+            return Ok(());
+        }
+
+        self.animate(ctx.dt, &mut ctx.scene.graph);
+
+        let Some(player) = self.find_player(&ctx.scene.graph) else {
+            return Ok(());
+        };
+
+        if !self.apply(player, ctx) {
+            return Ok(());
+        }
+
+        if let Some(sound) = self.sound.as_ref() {
+            let position = ctx.scene.graph[ctx.handle].global_position();
+            play_sound(
+                sound.clone(),
+                position,
+                &self.audio_bus,
+                &mut ctx.scene.graph,
+            );
+        }
+
+        match *self.respawn_time {
+            // Hidden pickups are not taken, because the timer is checked first.
+            Some(respawn_time) => {
+                self.respawn_timer = respawn_time.max(f32::EPSILON);
+                ctx.scene.graph[ctx.handle].set_visibility(false);
+            }
+            None => ctx.scene.graph.remove_node(ctx.handle),
+        }
+
+        Ok(())
+    }
+    // ANCHOR_END: on_update
+}
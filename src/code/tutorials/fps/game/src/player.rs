@@ -1,22 +1,55 @@
-use crate::weapon::ShootWeaponMessage;
+use crate::{
+    faction::{Faction, FactionId},
+    health::{DamageMessage, Health},
+    hud::HudMessage,
+    sound::{play_surface_sound, SoundTable},
+    weapon::{
+        DrawWeaponMessage, HolsterWeaponMessage, RecoilMessage, ReloadWeaponMessage,
+        ShootWeaponMessage, Weapon,
+    },
+    Game,
+};
+use common::{
+    character::{find_collider, CharacterController, CharacterInput, CharacterShape, Physics3d},
+    input::InputMap,
+    interaction::Interactor,
+};
 use fyrox::graph::SceneGraph;
 use fyrox::plugin::error::GameResult;
 use fyrox::{
+    asset::Resource,
     core::{
-        algebra::{UnitQuaternion, UnitVector3, Vector3},
+        algebra::{Matrix4, Point3, UnitQuaternion, UnitVector3, Vector3},
+        log::Log,
         pool::Handle,
         reflect::prelude::*,
         type_traits::prelude::*,
         variable::InheritableVariable,
         visitor::prelude::*,
     },
-    event::{DeviceEvent, ElementState, Event, MouseButton, WindowEvent},
-    keyboard::{KeyCode, PhysicalKey},
-    scene::{node::Node, rigidbody::RigidBody},
-    script::{ScriptContext, ScriptTrait},
+    engine::input::InputState,
+    event::{Event, MouseScrollDelta, WindowEvent},
+    resource::model::{ModelResource, ModelResourceExtension},
+    scene::{
+        camera::{Camera, PerspectiveProjection, Projection},
+        collider::{CapsuleShape, Collider, ColliderShape},
+        graph::{
+            physics::{Intersection, RayCastOptions},
+            Graph,
+        },
+        node::Node,
+        rigidbody::RigidBody,
+        Scene,
+    },
+    script::{
+        ScriptContext, ScriptDeinitContext, ScriptMessageContext, ScriptMessagePayload,
+        ScriptMessageSender, ScriptTrait,
+    },
 };
 
-#[derive(Visit, Reflect, Default, Debug, Clone, TypeUuidProvider, ComponentProvider)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Visit, Reflect, Debug, Clone, TypeUuidProvider, ComponentProvider)]
 #[type_uuid(id = "49cfe29e-c7c7-4317-8178-996251a0c2f9")]
 #[visit(optional)]
 pub struct Player {
@@ -49,75 +82,1018 @@ pub struct Player {
     // ANCHOR_END: current_weapon_field
     #[reflect(hidden)]
     shoot: bool,
+
+    // ANCHOR: health_field
+    #[component(include)]
+    health: Health,
+    // ANCHOR_END: health_field
+
+    // ANCHOR: faction_field
+    #[component(include)]
+    faction: Faction,
+    // ANCHOR_END: faction_field
+
+    // ANCHOR: remote_input_field
+    // Players of network clients are controlled by the input, that the clients send to the server.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub remote_input: Option<PlayerInput>,
+    // ANCHOR_END: remote_input_field
+
+    // ANCHOR: interactor_field
+    // Finds doors, switches and other usable objects in front of the camera.
+    interactor: Interactor,
+    // ANCHOR_END: interactor_field
+
+    // ANCHOR: armor_fields
+    // Armor takes a part of the incoming damage until it is depleted.
+    max_armor: InheritableVariable<f32>,
+    armor_absorption: InheritableVariable<f32>,
+    #[reflect(hidden)]
+    armor: f32,
+    // ANCHOR_END: armor_fields
+
+    // ANCHOR: knockback_field
+    // Additional velocity, that comes from hits and gradually fades out.
+    #[reflect(hidden)]
+    knockback: Vector3<f32>,
+    // ANCHOR_END: knockback_field
+
+    // ANCHOR: inventory_fields
+    // Every weapon the player has, including the current one.
+    weapons: InheritableVariable<Vec<Handle<Node>>>,
+
+    // A node to which new weapons will be attached.
+    weapon_pivot: InheritableVariable<Handle<Node>>,
+
+    // Total time (in seconds) of holstering the current weapon and drawing the next one.
+    switch_delay: InheritableVariable<f32>,
+
+    #[reflect(hidden)]
+    switch_timer: f32,
+
+    #[reflect(hidden)]
+    next_weapon: Handle<Node>,
+    // ANCHOR_END: inventory_fields
+
+    // ANCHOR: movement_fields
+    // Capsule collider of the player, it is used for ground checks and crouching.
+    collider: InheritableVariable<Handle<Node>>,
+
+    // Movement speeds in meters per second.
+    walk_speed: InheritableVariable<f32>,
+    sprint_speed: InheritableVariable<f32>,
+    crouch_speed: InheritableVariable<f32>,
+
+    // Initial vertical speed of a jump (in meters per second).
+    jump_speed: InheritableVariable<f32>,
+
+    // Acceleration, slopes, steps and moving platforms are handled by the controller.
+    controller: CharacterController,
+
+    // How much the capsule and the camera are lowered when crouching (in meters).
+    crouch_height: InheritableVariable<f32>,
+
+    // Maximum sprint duration (in seconds) and how much of it restores in one second.
+    max_stamina: InheritableVariable<f32>,
+    stamina_regeneration: InheritableVariable<f32>,
+
+    #[reflect(hidden)]
+    jump: bool,
+
+    #[reflect(hidden)]
+    crouch: bool,
+
+    #[reflect(hidden)]
+    sprint: bool,
+
+    #[reflect(hidden)]
+    crouching: bool,
+
+    #[reflect(hidden)]
+    stamina_spent: f32,
+
+    // Initial shape of the collider and height of the camera, they're used to stand up.
+    #[reflect(hidden)]
+    standing_capsule: Option<CapsuleShape>,
+
+    #[reflect(hidden)]
+    standing_camera_height: Option<f32>,
+    // ANCHOR_END: movement_fields
+
+    // ANCHOR: aiming_fields
+    // How fast (per second) the camera and the weapon blend to and from the aiming state.
+    aim_speed: InheritableVariable<f32>,
+
+    #[reflect(hidden)]
+    aim: bool,
+
+    // 0.0 - hip fire, 1.0 - fully aimed down the sights.
+    #[reflect(hidden)]
+    aim_factor: f32,
+
+    // Initial field of view of the camera (in radians) and position of the weapon pivot, they're
+    // restored when the player stops aiming.
+    #[reflect(hidden)]
+    default_fov: Option<f32>,
+
+    #[reflect(hidden)]
+    default_weapon_pivot_position: Option<Vector3<f32>>,
+    // ANCHOR_END: aiming_fields
+
+    // ANCHOR: footstep_fields
+    // Footstep sounds for each surface.
+    footstep_sounds: InheritableVariable<Option<Resource<SoundTable>>>,
+
+    // Distance (in meters), that the player walks between two steps.
+    step_length: InheritableVariable<f32>,
+
+    // Name of the audio bus, that plays the footsteps.
+    audio_bus: InheritableVariable<String>,
+
+    #[reflect(hidden)]
+    step_distance: f32,
+    // ANCHOR_END: footstep_fields
 }
 
-impl ScriptTrait for Player {
-    // ANCHOR: on_os_event
-    fn on_os_event(&mut self, event: &Event<()>, _ctx: &mut ScriptContext) -> GameResult {
-        match event {
-            // Raw mouse input is responsible for camera rotation.
-            Event::DeviceEvent {
-                event:
-                    DeviceEvent::MouseMotion {
-                        delta: (dx, dy), ..
-                    },
-                ..
-            } => {
-                // Pitch is responsible for vertical camera rotation. It has -89.9..89.0 degree limits,
-                // to prevent infinite rotation.
-                let mouse_speed = 0.35;
-                self.pitch = (self.pitch + *dy as f32 * mouse_speed).clamp(-89.9, 89.9);
-                self.yaw -= *dx as f32 * mouse_speed;
-            }
-            // Keyboard input is responsible for player's movement.
-            Event::WindowEvent {
-                event: WindowEvent::KeyboardInput { event, .. },
-                ..
-            } => {
-                if let PhysicalKey::Code(code) = event.physical_key {
-                    let is_pressed = event.state == ElementState::Pressed;
-                    match code {
-                        KeyCode::KeyW => self.move_forward = is_pressed,
-                        KeyCode::KeyS => self.move_backward = is_pressed,
-                        KeyCode::KeyA => self.move_left = is_pressed,
-                        KeyCode::KeyD => self.move_right = is_pressed,
-                        _ => (),
+impl Default for Player {
+    fn default() -> Self {
+        Self {
+            move_forward: false,
+            move_backward: false,
+            move_left: false,
+            move_right: false,
+            yaw: 0.0,
+            pitch: 0.0,
+            camera: Default::default(),
+            current_weapon: Default::default(),
+            shoot: false,
+            health: Default::default(),
+            faction: Faction::new(FactionId::Players),
+            remote_input: None,
+            interactor: Default::default(),
+            max_armor: 100.0.into(),
+            armor_absorption: 0.6.into(),
+            armor: 0.0,
+            knockback: Default::default(),
+            weapons: Default::default(),
+            weapon_pivot: Default::default(),
+            switch_delay: 0.5.into(),
+            switch_timer: 0.0,
+            next_weapon: Default::default(),
+            collider: Default::default(),
+            walk_speed: 4.0.into(),
+            sprint_speed: 7.0.into(),
+            crouch_speed: 2.0.into(),
+            jump_speed: 5.0.into(),
+            controller: Default::default(),
+            crouch_height: 0.6.into(),
+            max_stamina: 5.0.into(),
+            stamina_regeneration: 1.0.into(),
+            jump: false,
+            crouch: false,
+            sprint: false,
+            crouching: false,
+            stamina_spent: 0.0,
+            standing_capsule: None,
+            standing_camera_height: None,
+            aim_speed: 8.0.into(),
+            aim: false,
+            aim_factor: 0.0,
+            default_fov: None,
+            default_weapon_pivot_position: None,
+            footstep_sounds: Default::default(),
+            step_length: 1.8.into(),
+            audio_bus: "Primary".to_string().into(),
+            step_distance: 0.0,
+        }
+    }
+}
+
+// ANCHOR: give_weapon_message
+// Adds a new weapon to the inventory of a player.
+#[derive(Debug)]
+pub struct GiveWeaponMessage {
+    pub weapon: ModelResource,
+}
+impl ScriptMessagePayload for GiveWeaponMessage {}
+// ANCHOR_END: give_weapon_message
+
+// ANCHOR: pickup_messages
+// Restores the health of a player.
+#[derive(Debug)]
+pub struct HealMessage {
+    pub amount: f32,
+}
+impl ScriptMessagePayload for HealMessage {}
+
+// Adds armor to a player, up to its maximum.
+#[derive(Debug)]
+pub struct ArmorMessage {
+    pub amount: f32,
+}
+impl ScriptMessagePayload for ArmorMessage {}
+
+// Adds ammo to the weapon of a player, that was made from the given prefab. The ammo is lost if
+// the player has no such weapon.
+#[derive(Debug)]
+pub struct GiveAmmoMessage {
+    pub weapon: ModelResource,
+    pub amount: u32,
+}
+impl ScriptMessagePayload for GiveAmmoMessage {}
+// ANCHOR_END: pickup_messages
+
+// ANCHOR: capsule_ends
+// Returns bottom and top points of a capsule, the capsule could be oriented either way.
+fn capsule_ends(capsule: &CapsuleShape) -> (Vector3<f32>, Vector3<f32>) {
+    if capsule.begin.y <= capsule.end.y {
+        (capsule.begin, capsule.end)
+    } else {
+        (capsule.end, capsule.begin)
+    }
+}
+// ANCHOR_END: capsule_ends
+
+// ANCHOR: weapon_slot
+// Actions that select the weapons in the inventory, an index of an action is the index of the weapon.
+const WEAPON_SLOT_ACTIONS: [&str; 9] = [
+    "weapon_1", "weapon_2", "weapon_3", "weapon_4", "weapon_5", "weapon_6", "weapon_7", "weapon_8",
+    "weapon_9",
+];
+// ANCHOR_END: weapon_slot
+
+// ANCHOR: player_input
+// State of the controls of the player. Local players read it from the input map, while network
+// clients read it on their side and send it to the server.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct PlayerInput {
+    pub move_forward: bool,
+    pub move_backward: bool,
+    pub move_left: bool,
+    pub move_right: bool,
+    pub sprint: bool,
+    pub crouch: bool,
+    pub jump: bool,
+    pub shoot: bool,
+    pub aim: bool,
+    pub reload: bool,
+    pub interact: bool,
+    pub weapon_slot: Option<usize>,
+    // Angles (in degrees) of the camera, they're accumulated from the mouse movement.
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl PlayerInput {
+    // Actions are bound to the keys in the input config of the game (see `Game::input`). The
+    // angles are continued from the given ones.
+    pub fn read(input: &InputMap, state: &InputState, yaw: f32, pitch: f32) -> Self {
+        Self {
+            move_forward: input.is_pressed("move_forward", state),
+            move_backward: input.is_pressed("move_backward", state),
+            move_left: input.is_pressed("move_left", state),
+            move_right: input.is_pressed("move_right", state),
+            sprint: input.is_pressed("sprint", state),
+            crouch: input.is_pressed("crouch", state),
+            jump: input.is_just_pressed("jump", state),
+            shoot: input.is_pressed("shoot", state),
+            aim: input.is_pressed("aim", state),
+            reload: input.is_just_pressed("reload", state),
+            interact: input.is_just_pressed("interact", state),
+            // ANCHOR: weapon_selection_keys
+            weapon_slot: WEAPON_SLOT_ACTIONS
+                .iter()
+                .position(|action| input.is_just_pressed(action, state)),
+            // ANCHOR_END: weapon_selection_keys
+            // Pitch is responsible for vertical camera rotation. It has -89.9..89.0 degree
+            // limits, to prevent infinite rotation.
+            pitch: (pitch + input.axis("look_vertical", state)).clamp(-89.9, 89.9),
+            yaw: yaw - input.axis("look_horizontal", state),
+        }
+    }
+
+    // One-shot actions (jumps, reloads, etc.) happen once, even if the input is applied many
+    // times.
+    fn take(&mut self) -> Self {
+        let input = self.clone();
+        self.jump = false;
+        self.reload = false;
+        self.interact = false;
+        self.weapon_slot = None;
+        input
+    }
+
+    // Replaces the input with a newer one. One-shot actions, that weren't applied yet, are kept,
+    // clients could send the input a few times per frame of the server.
+    pub fn merge(&mut self, newer: PlayerInput) {
+        *self = Self {
+            jump: self.jump || newer.jump,
+            reload: self.reload || newer.reload,
+            interact: self.interact || newer.interact,
+            weapon_slot: newer.weapon_slot.or(self.weapon_slot),
+            ..newer
+        };
+    }
+}
+// ANCHOR_END: player_input
+
+impl Player {
+    pub fn health(&self) -> &Health {
+        &self.health
+    }
+
+    pub fn armor(&self) -> f32 {
+        self.armor
+    }
+
+    pub fn max_armor(&self) -> f32 {
+        *self.max_armor
+    }
+
+    pub fn add_armor(&mut self, amount: f32) {
+        self.armor = (self.armor + amount.max(0.0)).min(*self.max_armor);
+    }
+
+    // ANCHOR: respawn
+    // Brings the dead player back to life, the caller moves the player to a spawn point.
+    pub fn respawn(&mut self) {
+        self.health.heal(self.health.max());
+        self.armor = 0.0;
+        self.knockback = Vector3::default();
+        self.controller.stop();
+    }
+    // ANCHOR_END: respawn
+
+    // ANCHOR: take_damage
+    // Returns `true` if the damage was lethal.
+    fn take_damage(&mut self, amount: f32) -> bool {
+        let absorbed = (amount * *self.armor_absorption).min(self.armor);
+        self.armor -= absorbed;
+        self.health.take_damage(amount - absorbed)
+    }
+    // ANCHOR_END: take_damage
+
+    // ANCHOR: update_hud
+    fn update_hud(&self, ctx: &mut ScriptContext) {
+        // The HUD shows the state of the local player, remote players are shown on their clients.
+        if ctx.plugins.get::<Game>().player != ctx.handle {
+            return;
+        }
+
+        let weapon = ctx
+            .scene
+            .graph
+            .try_get(*self.current_weapon)
+            .ok()
+            .and_then(|node| node.try_get_script::<Weapon>());
+
+        let hud = &mut ctx.plugins.get_mut::<Game>().hud;
+        hud.send(HudMessage::Health {
+            amount: self.health.amount(),
+            max: self.health.max(),
+        });
+        hud.send(HudMessage::Armor {
+            amount: self.armor,
+            max: *self.max_armor,
+        });
+        if let Some(weapon) = weapon {
+            hud.send(HudMessage::Ammo {
+                loaded: weapon.loaded_ammo(),
+                reserve: weapon.reserve_ammo(),
+            });
+            hud.send(HudMessage::Spread(weapon.spread_angle(self.is_aiming())));
+        }
+    }
+    // ANCHOR_END: update_hud
+
+    pub fn current_weapon(&self) -> Handle<Node> {
+        *self.current_weapon
+    }
+
+    fn is_switching_weapon(&self) -> bool {
+        self.switch_timer > 0.0
+    }
+
+    fn is_aiming(&self) -> bool {
+        self.aim && !self.is_switching_weapon()
+    }
+
+    // ANCHOR: update_aiming
+    fn update_aiming(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        let target_factor = if self.is_aiming() { 1.0 } else { 0.0 };
+        self.aim_factor += (target_factor - self.aim_factor) * (*self.aim_speed * ctx.dt).min(1.0);
+
+        let graph = &mut ctx.scene.graph;
+        // The player could have no weapon (or be switching weapons), there's nothing to aim then.
+        let Some((sight, aim_fov)) = graph
+            .try_get(*self.current_weapon)
+            .ok()
+            .and_then(|weapon| weapon.try_get_script::<Weapon>())
+            .map(|weapon| (weapon.sight(), weapon.aim_fov()))
+        else {
+            return Ok(());
+        };
+
+        // Zoom in by narrowing the field of view.
+        let camera = graph.try_get_mut_of_type::<Camera>(self.camera)?;
+        if let Projection::Perspective(perspective) = camera.projection().clone() {
+            let default_fov = *self.default_fov.get_or_insert(perspective.fov);
+            camera.set_projection(Projection::Perspective(PerspectiveProjection {
+                fov: default_fov + (aim_fov.to_radians() - default_fov) * self.aim_factor,
+                ..perspective
+            }));
+        }
+
+        // Move the weapon so the sight lies on the line of sight of the camera.
+        let Ok(sight) = graph.try_get(sight) else {
+            return Ok(());
+        };
+        let sight_position = sight.global_position();
+        let camera = graph.try_get(self.camera)?;
+        let camera_position = camera.global_position();
+        let look = camera
+            .look_vector()
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::z);
+        let closest_point_on_view_line =
+            camera_position + look.scale((sight_position - camera_position).dot(&look));
+        let correction = closest_point_on_view_line - sight_position;
+
+        let Ok(pivot) = graph.try_get(*self.weapon_pivot) else {
+            return Ok(());
+        };
+        // The correction is in world space, the pivot moves in the space of its parent.
+        let correction = graph
+            .try_get(pivot.parent())
+            .ok()
+            .and_then(|parent| parent.global_transform().try_inverse())
+            .unwrap_or_else(Matrix4::identity)
+            .transform_vector(&correction);
+        let current_position = **pivot.local_transform().position();
+        let default_position = *self
+            .default_weapon_pivot_position
+            .get_or_insert(current_position);
+        let aimed_position = current_position + correction;
+
+        if let Ok(pivot) = graph.try_get_mut(*self.weapon_pivot) {
+            pivot.local_transform_mut().set_position(
+                default_position + (aimed_position - default_position) * self.aim_factor,
+            );
+        }
+
+        Ok(())
+    }
+    // ANCHOR_END: update_aiming
+
+    // ANCHOR: begin_weapon_switch
+    fn begin_weapon_switch(&mut self, index: usize, message_sender: &ScriptMessageSender) {
+        if self.is_switching_weapon() {
+            return;
+        }
+
+        let Some(weapon) = self.weapons.get(index).cloned() else {
+            return;
+        };
+
+        if weapon == *self.current_weapon {
+            return;
+        }
+
+        if self.current_weapon.is_some() {
+            message_sender.send_to_target(*self.current_weapon, HolsterWeaponMessage {});
+        }
+
+        self.next_weapon = weapon;
+        self.switch_timer = *self.switch_delay;
+    }
+    // ANCHOR_END: begin_weapon_switch
+
+    // ANCHOR: cycle_weapons
+    fn cycle_weapons(&mut self, forward: bool, message_sender: &ScriptMessageSender) {
+        let count = self.weapons.len();
+        if count == 0 {
+            return;
+        }
+
+        let current = self
+            .weapons
+            .iter()
+            .position(|weapon| *weapon == *self.current_weapon)
+            .unwrap_or_default();
+
+        let next = if forward {
+            (current + 1) % count
+        } else {
+            (current + count - 1) % count
+        };
+
+        self.begin_weapon_switch(next, message_sender);
+    }
+    // ANCHOR_END: cycle_weapons
+
+    // ANCHOR: update_weapon_switch
+    fn update_weapon_switch(&mut self, ctx: &mut ScriptContext) {
+        if !self.is_switching_weapon() {
+            return;
+        }
+
+        // The first half of the delay is used to holster the current weapon, the second one - to
+        // draw the next weapon.
+        let half = *self.switch_delay * 0.5;
+        let was_holstering = self.switch_timer > half;
+        self.switch_timer -= ctx.dt;
+
+        if was_holstering && self.switch_timer <= half {
+            if let Ok(current_weapon) = ctx.scene.graph.try_get_mut(*self.current_weapon) {
+                current_weapon.set_visibility(false);
+            }
+
+            self.current_weapon
+                .set_value_and_mark_modified(self.next_weapon);
+            self.next_weapon = Handle::NONE;
+
+            if let Ok(current_weapon) = ctx.scene.graph.try_get_mut(*self.current_weapon) {
+                current_weapon.set_visibility(true);
+                ctx.message_sender
+                    .send_to_target(*self.current_weapon, DrawWeaponMessage {});
+            }
+        }
+    }
+    // ANCHOR_END: update_weapon_switch
+
+    pub fn stamina(&self) -> f32 {
+        (*self.max_stamina - self.stamina_spent).max(0.0)
+    }
+
+    // ANCHOR: cast_from_capsule
+    // Casts a ray from a point of the capsule (in local coordinates of the collider) and checks
+    // if it hits anything except the player itself.
+    fn cast_from_capsule(
+        &self,
+        point: Vector3<f32>,
+        direction: Vector3<f32>,
+        max_len: f32,
+        graph: &Graph,
+    ) -> bool {
+        self.first_hit_from_capsule(point, direction, max_len, graph)
+            .is_some()
+    }
+
+    // The same as `cast_from_capsule`, but returns the closest intersection.
+    fn first_hit_from_capsule(
+        &self,
+        point: Vector3<f32>,
+        direction: Vector3<f32>,
+        max_len: f32,
+        graph: &Graph,
+    ) -> Option<Intersection> {
+        let collider = graph.try_get(*self.collider).ok()?;
+
+        let origin = collider
+            .global_transform()
+            .transform_point(&Point3::from(point));
+
+        let mut intersections = Vec::new();
+        graph.physics.cast_ray(
+            RayCastOptions {
+                ray_origin: origin,
+                ray_direction: direction,
+                max_len,
+                groups: Default::default(),
+                sort_results: true,
+            },
+            &mut intersections,
+        );
+
+        intersections
+            .into_iter()
+            .find(|intersection| intersection.collider != *self.collider)
+    }
+    // ANCHOR_END: cast_from_capsule
+
+    fn capsule(&self, graph: &Graph) -> Option<CapsuleShape> {
+        match graph
+            .try_get_of_type::<Collider>(*self.collider)
+            .ok()?
+            .shape()
+        {
+            ColliderShape::Capsule(capsule) => Some(capsule.clone()),
+            _ => None,
+        }
+    }
+
+    // ANCHOR: play_footstep
+    fn play_footstep(&self, ctx: &mut ScriptContext) {
+        let Some(footstep_sounds) = self.footstep_sounds.as_ref() else {
+            return;
+        };
+        let Some(shape) = CharacterShape::from_collider(&ctx.scene.graph, *self.collider) else {
+            return;
+        };
+
+        // The sound depends on the surface right under the player.
+        play_surface_sound(
+            footstep_sounds,
+            self.controller.ground(),
+            shape.feet,
+            &self.audio_bus,
+            ctx.resource_manager,
+            &mut ctx.scene.graph,
+        );
+    }
+    // ANCHOR_END: play_footstep
+
+    // ANCHOR: update_crouch
+    fn update_crouch(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        if self.crouch != self.crouching {
+            let Some(capsule) = self.capsule(&ctx.scene.graph) else {
+                return Ok(());
+            };
+
+            let standing_capsule = self
+                .standing_capsule
+                .get_or_insert_with(|| capsule.clone())
+                .clone();
+
+            let can_change = if self.crouch {
+                true
+            } else {
+                // Stand up only if there's enough room above the head.
+                let (_, top) = capsule_ends(&capsule);
+                !self.cast_from_capsule(
+                    top,
+                    Vector3::y(),
+                    capsule.radius + *self.crouch_height,
+                    &ctx.scene.graph,
+                )
+            };
+
+            if can_change {
+                let new_capsule = if self.crouch {
+                    // Lower the top of the capsule, the bottom stays on the ground.
+                    let mut crouching_capsule = standing_capsule.clone();
+                    let (bottom, top) = capsule_ends(&standing_capsule);
+                    let new_top_height = (top.y - *self.crouch_height).max(bottom.y);
+                    if crouching_capsule.end.y >= crouching_capsule.begin.y {
+                        crouching_capsule.end.y = new_top_height;
+                    } else {
+                        crouching_capsule.begin.y = new_top_height;
                     }
+                    crouching_capsule
+                } else {
+                    standing_capsule
+                };
+
+                ctx.scene
+                    .graph
+                    .try_get_mut_of_type::<Collider>(*self.collider)?
+                    .set_shape(ColliderShape::Capsule(new_capsule));
+
+                self.crouching = self.crouch;
+            }
+        }
+
+        // Smoothly move the camera up or down.
+        let camera = ctx.scene.graph.try_get_mut(self.camera)?;
+        let transform = camera.local_transform_mut();
+        let mut position = **transform.position();
+        let standing_camera_height = *self.standing_camera_height.get_or_insert(position.y);
+        let target_height = if self.crouching {
+            standing_camera_height - *self.crouch_height
+        } else {
+            standing_camera_height
+        };
+        position.y += (target_height - position.y) * (10.0 * ctx.dt).min(1.0);
+        transform.set_position(position);
+
+        Ok(())
+    }
+    // ANCHOR_END: update_crouch
+
+    // ANCHOR: give_weapon
+    fn give_weapon(
+        &mut self,
+        prefab: &ModelResource,
+        self_handle: Handle<Node>,
+        scene: &mut Scene,
+        message_sender: &ScriptMessageSender,
+    ) {
+        // Do not add duplicates, just give some ammo instead.
+        for weapon in self.weapons.iter() {
+            if let Ok(weapon_node) = scene.graph.try_get_mut(*weapon) {
+                if weapon_node.resource().as_ref() == Some(prefab) {
+                    if let Some(weapon) = weapon_node.try_get_script_mut::<Weapon>() {
+                        weapon.add_magazine();
+                    }
+                    return;
                 }
             }
-            _ => {}
         }
-        // ...
-        // ANCHOR_END: on_os_event
 
-        // ANCHOR: shooting
+        // New weapons go to the pivot, or next to the current weapon if the pivot is not set.
+        let pivot = if scene.graph.try_get(*self.weapon_pivot).is_ok() {
+            *self.weapon_pivot
+        } else {
+            match scene.graph.try_get(*self.current_weapon) {
+                Ok(current_weapon) => current_weapon.parent(),
+                Err(_) => {
+                    Log::warn("The player has no weapon pivot, the weapon cannot be given.");
+                    return;
+                }
+            }
+        };
+
+        let weapon = prefab.instantiate(scene);
+        scene.graph.link_nodes(weapon, pivot);
+
+        if let Ok(weapon_node) = scene.graph.try_get_mut(weapon) {
+            weapon_node.set_visibility(false);
+            if let Some(weapon_script) = weapon_node.try_get_script_mut::<Weapon>() {
+                weapon_script.set_owner(self_handle);
+            }
+        }
+
+        self.weapons.push(weapon);
+
+        // Take the new weapon right away if there's nothing in hands.
+        if self.current_weapon.is_none() {
+            self.begin_weapon_switch(self.weapons.len() - 1, message_sender);
+        }
+    }
+    // ANCHOR_END: give_weapon
+
+    // ANCHOR: give_ammo
+    fn give_ammo(&self, prefab: &ModelResource, amount: u32, scene: &mut Scene) {
+        for weapon in self.weapons.iter() {
+            if let Ok(weapon_node) = scene.graph.try_get_mut(*weapon) {
+                if weapon_node.resource().as_ref() == Some(prefab) {
+                    if let Some(weapon) = weapon_node.try_get_script_mut::<Weapon>() {
+                        weapon.add_ammo(amount);
+                    }
+                    return;
+                }
+            }
+        }
+    }
+    // ANCHOR_END: give_ammo
+}
+
+impl ScriptTrait for Player {
+    // ANCHOR: on_start
+    fn on_start(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        // Let the game know whom to watch for the game over. Players of network clients are
+        // tracked by the deathmatch instead, clients are told which player is theirs.
+        let game = ctx.plugins.get_mut::<Game>();
+        if self.remote_input.is_none() && !game.is_network_client() {
+            game.player = ctx.handle;
+        }
+        game.factions.register(ctx.handle, self.faction.id());
+
+        ctx.message_dispatcher
+            .subscribe_to::<DamageMessage>(ctx.handle);
+        ctx.message_dispatcher
+            .subscribe_to::<GiveWeaponMessage>(ctx.handle);
+        ctx.message_dispatcher
+            .subscribe_to::<RecoilMessage>(ctx.handle);
+        ctx.message_dispatcher
+            .subscribe_to::<HealMessage>(ctx.handle);
+        ctx.message_dispatcher
+            .subscribe_to::<ArmorMessage>(ctx.handle);
+        ctx.message_dispatcher
+            .subscribe_to::<GiveAmmoMessage>(ctx.handle);
+
+        let collider = find_collider(&ctx.scene.graph, ctx.handle, *self.collider);
+        self.collider.set_value_silent(collider);
+
+        // The current weapon is always in the inventory.
+        if self.current_weapon.is_some() && !self.weapons.contains(&*self.current_weapon) {
+            let current_weapon = *self.current_weapon;
+            self.weapons.push(current_weapon);
+        }
+
+        for weapon in self.weapons.iter() {
+            if let Ok(weapon_node) = ctx.scene.graph.try_get_mut(*weapon) {
+                // Only the current weapon is visible, the rest are in the "backpack".
+                weapon_node.set_visibility(*weapon == *self.current_weapon);
+
+                // Let the weapon know who's holding it.
+                if let Some(weapon) = weapon_node.try_get_script_mut::<Weapon>() {
+                    weapon.set_owner(ctx.handle);
+                }
+            }
+        }
+        Ok(())
+    }
+    // ANCHOR_END: on_start
+
+    fn on_deinit(&mut self, ctx: &mut ScriptDeinitContext) -> GameResult {
+        ctx.plugins
+            .get_mut::<Game>()
+            .factions
+            .unregister(ctx.node_handle);
+        Ok(())
+    }
+
+    // ANCHOR: on_message
+    fn on_message(
+        &mut self,
+        message: &mut dyn ScriptMessagePayload,
+        ctx: &mut ScriptMessageContext,
+    ) -> GameResult {
+        if let Some(damage) = message.downcast_ref::<DamageMessage>() {
+            if !ctx
+                .plugins
+                .get::<Game>()
+                .factions
+                .can_damage(damage.dealer, ctx.handle)
+            {
+                return Ok(());
+            }
+            if self.take_damage(damage.amount) {
+                let killer = ctx
+                    .scene
+                    .graph
+                    .try_get(damage.dealer)
+                    .map(|dealer| dealer.name().to_owned())
+                    .unwrap_or_default();
+                let victim = ctx.scene.graph[ctx.handle].name().to_owned();
+                let game = ctx.plugins.get_mut::<Game>();
+                game.hud.send(HudMessage::Kill { killer, victim });
+                // ANCHOR: deathmatch_kill
+                if let Some(deathmatch) = game.deathmatch.as_mut() {
+                    deathmatch.on_player_killed(damage.dealer, ctx.handle);
+                }
+                // ANCHOR_END: deathmatch_kill
+            }
+            self.knockback += damage.knockback;
+        } else if let Some(give_weapon) = message.downcast_ref::<GiveWeaponMessage>() {
+            self.give_weapon(
+                &give_weapon.weapon,
+                ctx.handle,
+                ctx.scene,
+                ctx.message_sender,
+            );
+        } else if let Some(recoil) = message.downcast_ref::<RecoilMessage>() {
+            // ANCHOR: recoil_kick
+            // Negative pitch looks up.
+            self.pitch = (self.pitch - recoil.pitch).clamp(-89.9, 89.9);
+            self.yaw += recoil.yaw;
+            // ANCHOR_END: recoil_kick
+        } else if let Some(heal) = message.downcast_ref::<HealMessage>() {
+            // Dead players stay dead.
+            if !self.health.is_dead() {
+                self.health.heal(heal.amount);
+            }
+        } else if let Some(armor) = message.downcast_ref::<ArmorMessage>() {
+            if !self.health.is_dead() {
+                self.add_armor(armor.amount);
+            }
+        } else if let Some(give_ammo) = message.downcast_ref::<GiveAmmoMessage>() {
+            self.give_ammo(&give_ammo.weapon, give_ammo.amount, ctx.scene);
+        }
+        Ok(())
+    }
+    // ANCHOR_END: on_message
+
+    // ANCHOR: on_os_event
+    fn on_os_event(&mut self, event: &Event<()>, ctx: &mut ScriptContext) -> GameResult {
+        // ANCHOR: weapon_selection_wheel
+        // Mouse wheel is not an action, it has no pressed state.
         if let Event::WindowEvent {
-            event:
-                WindowEvent::MouseInput {
-                    state,
-                    button: MouseButton::Left,
-                    ..
-                },
+            event: WindowEvent::MouseWheel { delta, .. },
             ..
         } = event
         {
-            self.shoot = *state == ElementState::Pressed;
+            // Remote players are controlled by their clients.
+            if !ctx.plugins.get::<Game>().is_simulating() || self.remote_input.is_some() {
+                return Ok(());
+            }
+
+            let amount = match delta {
+                MouseScrollDelta::LineDelta(_, y) => *y,
+                MouseScrollDelta::PixelDelta(position) => position.y as f32,
+            };
+            if amount != 0.0 {
+                self.cycle_weapons(amount < 0.0, ctx.message_sender);
+            }
         }
-        // ANCHOR_END: shooting
+        // ANCHOR_END: weapon_selection_wheel
 
         Ok(())
     }
+    // ANCHOR_END: on_os_event
 
     // ANCHOR: on_update_begin
     fn on_update(&mut self, ctx: &mut ScriptContext) -> GameResult {
         // ANCHOR_END: on_update_begin
 
-        // ANCHOR: shooting_on_update
-        if self.shoot {
+        // Menus are opened or the server simulates the world, it is frozen here.
+        if !ctx.plugins.get::<Game>().is_simulating() {
+            return Ok(());
+        }
+
+        // ANCHOR: input
+        let input = PlayerInput::read(
+            &ctx.plugins.get::<Game>().input,
+            ctx.input_state,
+            self.yaw,
+            self.pitch,
+        );
+        // ANCHOR_END: input
+
+        // ANCHOR: remote_input
+        // Players of the other peers are controlled by the input, that comes from the network.
+        let input = match self.remote_input.as_mut() {
+            Some(remote_input) => remote_input.take(),
+            None => input,
+        };
+        // ANCHOR_END: remote_input
+
+        // ANCHOR: apply_input
+        self.move_forward = input.move_forward;
+        self.move_backward = input.move_backward;
+        self.move_left = input.move_left;
+        self.move_right = input.move_right;
+        self.pitch = input.pitch;
+        self.yaw = input.yaw;
+        // ANCHOR_END: apply_input
+
+        // ANCHOR: apply_actions
+        self.sprint = input.sprint;
+        self.crouch = input.crouch;
+        self.jump = input.jump;
+        self.shoot = input.shoot;
+        self.aim = input.aim;
+
+        if let Some(index) = input.weapon_slot {
+            self.begin_weapon_switch(index, ctx.message_sender);
+        }
+
+        if input.reload {
             ctx.message_sender
-                .send_to_target(*self.current_weapon, ShootWeaponMessage {});
+                .send_to_target(*self.current_weapon, ReloadWeaponMessage {});
         }
-        // ANCHOR_END: shooting_on_update
+        // ANCHOR_END: apply_actions
+
+        self.update_hud(ctx);
+
+        // ANCHOR: death
+        // Dead players cannot move or shoot, the body just falls down.
+        if self.health.is_dead() {
+            let rigid_body = ctx
+                .scene
+                .graph
+                .try_get_mut_of_type::<RigidBody>(ctx.handle)?;
+            let y_vel = rigid_body.lin_vel().y;
+            rigid_body.set_lin_vel(Vector3::new(0.0, y_vel, 0.0));
+            self.controller.stop();
+            let game = ctx.plugins.get_mut::<Game>();
+            if game.player == ctx.handle {
+                game.hud.send(HudMessage::Prompt(None));
+            }
+            return Ok(());
+        }
+        // ANCHOR_END: death
+
+        // ANCHOR: interaction
+        let camera = ctx.scene.graph.try_get(self.camera)?;
+        let prompt = self
+            .interactor
+            .update(
+                ctx.handle,
+                camera.global_position(),
+                camera.look_vector(),
+                &ctx.scene.graph,
+            )
+            .map(|prompt| prompt.to_owned());
+        let game = ctx.plugins.get_mut::<Game>();
+        if game.player == ctx.handle {
+            game.hud.send(HudMessage::Prompt(prompt));
+        }
+        if input.interact {
+            self.interactor.interact(ctx.handle, ctx.message_sender);
+        }
+        // ANCHOR_END: interaction
+
+        // ANCHOR: weapon_switch_on_update
+        self.update_weapon_switch(ctx);
+        // ANCHOR_END: weapon_switch_on_update
+
+        // ANCHOR: shooting
+        if self.shoot && !self.is_switching_weapon() {
+            ctx.message_sender.send_to_target(
+                *self.current_weapon,
+                ShootWeaponMessage {
+                    aiming: self.is_aiming(),
+                },
+            );
+        }
+        // ANCHOR_END: shooting
+
+        // ANCHOR: aiming_on_update
+        self.update_aiming(ctx)?;
+        // ANCHOR_END: aiming_on_update
 
         // ANCHOR: camera_rotation
         let camera = ctx.scene.graph.try_get_mut(self.camera)?;
@@ -135,11 +1111,11 @@ impl ScriptTrait for Player {
         // ANCHOR_END: camera_rotation
 
         // ANCHOR: on_update_end
-        // Borrow the node to which this script is assigned to. We also check if the node is RigidBody.
-        let rigid_body = ctx
-            .scene
-            .graph
-            .try_get_mut_of_type::<RigidBody>(ctx.handle)?;
+        // ANCHOR: ground_and_crouch
+        self.update_crouch(ctx)?;
+        let on_ground = self.controller.is_on_ground();
+        // ANCHOR_END: ground_and_crouch
+
         // Form a new velocity vector that corresponds to the pressed buttons.
         let mut velocity = Vector3::new(0.0, 0.0, 0.0);
         if self.move_forward {
@@ -155,20 +1131,114 @@ impl ScriptTrait for Player {
             velocity -= side_vector;
         }
 
-        let y_vel = rigid_body.lin_vel().y;
-        if let Some(normalized_velocity) = velocity.try_normalize(f32::EPSILON) {
-            let movement_speed = 240.0 * ctx.dt;
-            rigid_body.set_lin_vel(Vector3::new(
-                normalized_velocity.x * movement_speed,
-                y_vel,
-                normalized_velocity.z * movement_speed,
-            ));
+        // ANCHOR: sprint
+        let is_moving = velocity.norm_squared() > 0.0;
+        let is_sprinting = self.sprint
+            && self.move_forward
+            && on_ground
+            && !self.crouching
+            && self.stamina() > 0.0;
+        if is_sprinting && is_moving {
+            self.stamina_spent = (self.stamina_spent + ctx.dt).min(*self.max_stamina);
         } else {
-            // Hold player in-place in XZ plane when no button is pressed.
-            rigid_body.set_lin_vel(Vector3::new(0.0, y_vel, 0.0));
+            self.stamina_spent =
+                (self.stamina_spent - *self.stamina_regeneration * ctx.dt).max(0.0);
         }
 
+        let movement_speed = if self.crouching {
+            *self.crouch_speed
+        } else if is_sprinting {
+            *self.sprint_speed
+        } else {
+            *self.walk_speed
+        };
+        // ANCHOR_END: sprint
+
+        // ANCHOR: air_control
+        // The camera looks up and down, but the player walks horizontally.
+        let desired_velocity = Vector3::new(velocity.x, 0.0, velocity.z)
+            .try_normalize(f32::EPSILON)
+            .map(|direction| direction.scale(movement_speed))
+            .unwrap_or_default();
+        // ANCHOR_END: air_control
+
+        // ANCHOR: jump
+        // Jumps are not buffered, the button should be pressed while on the ground.
+        let jump_speed = (self.jump && !self.crouching).then_some(*self.jump_speed);
+        self.jump = false;
+        // ANCHOR_END: jump
+
+        // ANCHOR: character_controller
+        let Some(shape) = CharacterShape::from_collider(&ctx.scene.graph, *self.collider) else {
+            return Ok(());
+        };
+        let rigid_body = ctx.scene.graph.try_get_of_type::<RigidBody>(ctx.handle)?;
+        let movement = self.controller.update(
+            &Physics3d::new(&ctx.scene.graph, ctx.handle),
+            shape,
+            CharacterInput {
+                // The knockback is added to the desired velocity, so it pushes the player once
+                // instead of piling up in the velocity of the body.
+                desired_velocity: desired_velocity + self.knockback,
+                jump_speed,
+            },
+            rigid_body.lin_vel(),
+            ctx.dt,
+        );
+        movement.apply(
+            ctx.scene
+                .graph
+                .try_get_mut_of_type::<RigidBody>(ctx.handle)?,
+        );
+        // ANCHOR_END: character_controller
+
+        // Knockback fades out quickly, so the hits feel like pushes.
+        self.knockback = self.knockback.scale((1.0 - 8.0 * ctx.dt).max(0.0));
+
+        // ANCHOR: footsteps
+        // Steps are spaced by the travelled distance, so faster movement makes more frequent steps.
+        if self.controller.is_on_ground() {
+            self.step_distance += self.controller.velocity().norm() * ctx.dt;
+            if self.step_distance >= *self.step_length {
+                self.step_distance = 0.0;
+                self.play_footstep(ctx);
+            }
+        }
+        // ANCHOR_END: footsteps
+
         Ok(())
     }
     // ANCHOR_END: on_update_end
 }
+
+// Players in a known state for the save test, see `save.rs`.
+#[cfg(test)]
+impl Player {
+    // A hurt and tired player, that is switching to the given weapon.
+    pub(crate) fn mid_fight(weapon: Handle<Node>) -> Self {
+        let mut player = Self {
+            yaw: 1.0,
+            pitch: -0.25,
+            armor: 20.0,
+            switch_timer: 0.1,
+            next_weapon: weapon,
+            stamina_spent: 1.5,
+            ..Default::default()
+        };
+        player.health.take_damage(45.0);
+        player.weapons.set_value_and_mark_modified(vec![weapon]);
+        player.current_weapon.set_value_and_mark_modified(weapon);
+        player
+    }
+
+    // The fields are hidden from the reflection, so they are compared one by one.
+    pub(crate) fn assert_same_runtime_state(&self, other: &Self) {
+        assert_eq!(self.health.amount(), other.health.amount());
+        assert_eq!(self.yaw, other.yaw);
+        assert_eq!(self.pitch, other.pitch);
+        assert_eq!(self.armor, other.armor);
+        assert_eq!(self.switch_timer, other.switch_timer);
+        assert_eq!(self.next_weapon, other.next_weapon);
+        assert_eq!(self.stamina_spent, other.stamina_spent);
+    }
+}
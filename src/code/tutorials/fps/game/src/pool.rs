@@ -0,0 +1,233 @@
+use fyrox::{
+    core::{
+        algebra::{UnitQuaternion, Vector3},
+        pool::Handle,
+    },
+    graph::SceneGraph,
+    resource::model::{ModelResource, ModelResourceExtension},
+    scene::{
+        animation::AnimationPlayer, graph::Graph, node::Node, particle_system::ParticleSystem,
+        Scene,
+    },
+};
+use std::collections::HashSet;
+
+// ANCHOR: prefab_pool
+// Instances of a single prefab. Free instances are disabled, so they are neither rendered nor
+// updated, nor simulated by physics.
+#[derive(Debug, Clone)]
+struct PrefabPool {
+    prefab: ModelResource,
+    // Every instance of the pool, both free and in use.
+    instances: HashSet<Handle<Node>>,
+    free: Vec<Handle<Node>>,
+}
+
+impl PrefabPool {
+    fn instantiate(&mut self, scene: &mut Scene) -> Handle<Node> {
+        let instance = self.prefab.instantiate(scene);
+        self.instances.insert(instance);
+        instance
+    }
+}
+
+// Projectiles and effects are spawned many times per second, creating and deleting them each time
+// leaves the scene graph growing without bound (if nobody deletes them) or wastes time on
+// instantiation. Instead, the instances are taken from the pools and returned back when they're no
+// longer needed.
+#[derive(Default, Debug, Clone)]
+pub struct PrefabPools {
+    pools: Vec<PrefabPool>,
+}
+
+impl PrefabPools {
+    // Must be called when the scene changes, the instances belong to the old scene.
+    pub fn clear(&mut self) {
+        self.pools.clear();
+    }
+
+    fn pool_mut(&mut self, prefab: &ModelResource) -> &mut PrefabPool {
+        let index = match self.pools.iter().position(|pool| pool.prefab == *prefab) {
+            Some(index) => index,
+            None => {
+                self.pools.push(PrefabPool {
+                    prefab: prefab.clone(),
+                    instances: Default::default(),
+                    free: Default::default(),
+                });
+                self.pools.len() - 1
+            }
+        };
+        &mut self.pools[index]
+    }
+
+    // Creates instances of the prefab beforehand, so the pool has at least `count` of them.
+    pub fn prewarm(&mut self, prefab: &ModelResource, count: usize, scene: &mut Scene) {
+        let pool = self.pool_mut(prefab);
+        while pool.instances.len() < count {
+            let instance = pool.instantiate(scene);
+            scene.graph[instance].set_enabled(false);
+            pool.free.push(instance);
+        }
+    }
+
+    // Takes a free instance of the prefab from the pool and puts it at the given position. A new
+    // instance is created if there are no free ones.
+    pub fn spawn(
+        &mut self,
+        prefab: &ModelResource,
+        position: Vector3<f32>,
+        rotation: UnitQuaternion<f32>,
+        scene: &mut Scene,
+    ) -> Handle<Node> {
+        let pool = self.pool_mut(prefab);
+
+        let mut instance = Handle::NONE;
+        while let Some(free) = pool.free.pop() {
+            // The instance could be deleted by someone else, forget about it in this case.
+            if scene.graph.try_get(free).is_ok() {
+                instance = free;
+                break;
+            }
+            pool.instances.remove(&free);
+        }
+        if instance.is_none() {
+            instance = pool.instantiate(scene);
+        }
+
+        reset_instance(instance, &mut scene.graph);
+
+        let node = &mut scene.graph[instance];
+        node.set_enabled(true);
+        node.local_transform_mut()
+            .set_position(position)
+            .set_rotation(rotation);
+        // Scripts of the instance could use its global position before the next graph update.
+        scene
+            .graph
+            .update_hierarchical_data_for_descendants(instance);
+
+        instance
+    }
+
+    // Returns the node to its pool, nodes that do not belong to any pool are removed.
+    pub fn despawn(&mut self, node: Handle<Node>, graph: &mut Graph) {
+        let Some(pool) = self
+            .pools
+            .iter_mut()
+            .find(|pool| pool.instances.contains(&node))
+        else {
+            graph.remove_node(node);
+            return;
+        };
+
+        if !pool.free.contains(&node) {
+            if let Ok(node_ref) = graph.try_get_mut(node) {
+                node_ref.set_enabled(false);
+                pool.free.push(node);
+            }
+        }
+    }
+}
+
+// Reused instances must start from scratch, otherwise the particles of the previous use would pop
+// up at the new place and finished animations (such as trail dissolving) would not play again.
+fn reset_instance(root: Handle<Node>, graph: &mut Graph) {
+    let descendants = graph.traverse_handle_iter(root).collect::<Vec<_>>();
+    for handle in descendants {
+        if let Ok(particle_system) = graph.try_get_mut_of_type::<ParticleSystem>(handle) {
+            particle_system.clear_particles();
+        } else if let Ok(animation_player) = graph.try_get_mut_of_type::<AnimationPlayer>(handle) {
+            for animation in animation_player
+                .animations_mut()
+                .get_value_mut_silent()
+                .iter_mut()
+            {
+                animation.rewind();
+            }
+        }
+    }
+}
+// ANCHOR_END: prefab_pool
+
+#[cfg(test)]
+mod test {
+    use super::PrefabPools;
+    use fyrox::{
+        asset::untyped::ResourceKind,
+        core::{
+            algebra::{UnitQuaternion, Vector3},
+            uuid::Uuid,
+        },
+        resource::model::{Model, ModelResource, ModelResourceExtension, NodeMapping},
+        scene::{base::BaseBuilder, pivot::PivotBuilder, Scene},
+    };
+    use std::time::{Duration, Instant};
+
+    // Shots per iteration, each of them is spawned and then returned back.
+    const BURST: usize = 32;
+    const ITERATIONS: usize = 1000;
+
+    // A bare prefab, that looks like a projectile: a root with a few children.
+    fn projectile_prefab() -> ModelResource {
+        let mut scene = Scene::new();
+        let children = (0..4)
+            .map(|i| {
+                PivotBuilder::new(BaseBuilder::new().with_name(format!("Part{i}")))
+                    .build(&mut scene.graph)
+            })
+            .collect::<Vec<_>>();
+        PivotBuilder::new(
+            BaseBuilder::new()
+                .with_name("Projectile")
+                .with_children(children),
+        )
+        .build(&mut scene.graph);
+        ModelResource::new_ok(
+            Uuid::new_v4(),
+            ResourceKind::Embedded,
+            Model::new(NodeMapping::UseNames, scene),
+        )
+    }
+
+    fn measure(mut shoot: impl FnMut(&mut Scene)) -> Duration {
+        let mut scene = Scene::new();
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            shoot(&mut scene);
+        }
+        start.elapsed()
+    }
+
+    // Compares pooled projectiles with instantiating and removing them each time. Run it with
+    // `cargo test --release -- --ignored --nocapture pooled_vs_unpooled`.
+    #[test]
+    #[ignore]
+    fn pooled_vs_unpooled() {
+        let prefab = projectile_prefab();
+
+        let unpooled = measure(|scene| {
+            let instances = (0..BURST)
+                .map(|_| prefab.instantiate_at(scene, Vector3::zeros(), UnitQuaternion::identity()))
+                .collect::<Vec<_>>();
+            for instance in instances {
+                scene.graph.remove_node(instance);
+            }
+        });
+
+        let mut pools = PrefabPools::default();
+        let pooled = measure(|scene| {
+            let instances = (0..BURST)
+                .map(|_| pools.spawn(&prefab, Vector3::zeros(), UnitQuaternion::identity(), scene))
+                .collect::<Vec<_>>();
+            for instance in instances {
+                pools.despawn(instance, &mut scene.graph);
+            }
+        });
+
+        println!(
+            "{} shots: unpooled {unpooled:?}, pooled {pooled:?}",
+            BURST * ITERATIONS
+        );
+    }
+}
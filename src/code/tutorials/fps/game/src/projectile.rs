@@ -1,16 +1,55 @@
+use crate::{
+    decal::{spawn_impact, ImpactTable},
+    health::{DamageHit, DamageMessage},
+    sound::{play_surface_sound, SoundTable},
+    Game,
+};
 use fyrox::graph::SceneGraph;
-use fyrox::plugin::error::GameResult;
+use fyrox::plugin::error::{GameError, GameResult};
 use fyrox::{
+    asset::Resource,
     core::{
-        algebra::Vector3, math, pool::Handle, reflect::prelude::*, type_traits::prelude::*,
-        variable::InheritableVariable, visitor::prelude::*,
+        algebra::Vector3, log::Log, math, pool::Handle, reflect::prelude::*,
+        type_traits::prelude::*, variable::InheritableVariable, visitor::prelude::*,
+    },
+    resource::model::ModelResource,
+    scene::{
+        base::BaseBuilder,
+        collider::{Collider, ColliderBuilder, ColliderShape},
+        graph::physics::RayCastOptions,
+        node::Node,
+        rigidbody::RigidBody,
     },
-    resource::model::{ModelResource, ModelResourceExtension},
-    scene::{graph::physics::RayCastOptions, node::Node, rigidbody::RigidBody},
-    script::{ScriptContext, ScriptTrait},
+    script::{RoutingStrategy, ScriptContext, ScriptTrait},
 };
+use strum_macros::{AsRefStr, EnumString, VariantNames};
+
+// ANCHOR: projectile_mode
+#[derive(
+    Visit,
+    Reflect,
+    Default,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    AsRefStr,
+    EnumString,
+    VariantNames,
+    TypeUuidProvider,
+)]
+#[type_uuid(id = "8b1f3c52-6d0e-4a8f-9c27-5e4b7a1d2f90")]
+pub enum ProjectileMode {
+    // The projectile ray-casts once and hits instantly.
+    #[default]
+    Hitscan,
+    // The projectile is a rigid body, that flies until it hits something or its fuse runs out and
+    // then explodes.
+    Physical,
+}
+// ANCHOR_END: projectile_mode
 
-#[derive(Visit, Reflect, Default, Debug, Clone, TypeUuidProvider, ComponentProvider)]
+#[derive(Visit, Reflect, Debug, Clone, TypeUuidProvider, ComponentProvider)]
 #[type_uuid(id = "55199744-33be-4c1a-832a-727fe5f0ea28")]
 #[visit(optional)]
 pub struct Projectile {
@@ -21,11 +60,276 @@ pub struct Projectile {
     // ANCHOR: effect_field
     impact_effect: InheritableVariable<Option<ModelResource>>,
     // ANCHOR_END: effect_field
+
+    // ANCHOR: impact_table_field
+    // Decals and effects for each surface, `impact_effect` is used for surfaces, that aren't in
+    // the table.
+    impacts: InheritableVariable<Option<Resource<ImpactTable>>>,
+    // ANCHOR_END: impact_table_field
+
+    // ANCHOR: impact_sound_fields
+    // Sounds of the impact for each surface.
+    impact_sounds: InheritableVariable<Option<Resource<SoundTable>>>,
+    audio_bus: InheritableVariable<String>,
+    // ANCHOR_END: impact_sound_fields
+
+    // ANCHOR: damage_fields
+    // These fields are set by a weapon that fired the projectile.
+    #[reflect(hidden)]
+    pub damage: f32,
+
+    #[reflect(hidden)]
+    pub owner: Handle<Node>,
+
+    // Set by the weapon as well, physical projectiles require a rigid body as the root node of
+    // the prefab.
+    #[reflect(hidden)]
+    pub mode: ProjectileMode,
+    // ANCHOR_END: damage_fields
+
+    // ANCHOR: fired_field
+    // Projectiles are taken from a pool, so the same projectile is fired many times. The flag is
+    // reset by the weapon every time the projectile is spawned.
+    #[reflect(hidden)]
+    fired: bool,
+    // ANCHOR_END: fired_field
+
+    // Set by network clients for their copies of the shots of the server, such projectiles only
+    // show the trail and the impact.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub visual_only: bool,
+
+    // ANCHOR: physical_fields
+    // Sensor collider of the physical projectile, it is used to detect contacts.
+    sensor: InheritableVariable<Handle<Collider>>,
+
+    // Initial speed of the physical projectile (in meters per second).
+    launch_speed: InheritableVariable<f32>,
+
+    // Physical projectile explodes when its fuse runs out (in seconds). Rockets should explode
+    // on impact, while grenades should bounce around until the fuse runs out.
+    fuse_time: InheritableVariable<f32>,
+    explode_on_impact: InheritableVariable<bool>,
+
+    // Everything within this radius receives damage and impulse, that linearly decrease with the
+    // distance from the center of the explosion.
+    splash_radius: InheritableVariable<f32>,
+    splash_impulse: InheritableVariable<f32>,
+
+    // A sensor of the splash radius, that finds the victims of the explosion. It is created on
+    // the first launch of the projectile.
+    #[reflect(hidden)]
+    splash_sensor: Handle<Collider>,
+
+    #[reflect(hidden)]
+    lifetime: f32,
+
+    // The missing sensor is reported only once, not on every frame.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    sensor_warning_shown: bool,
+    // ANCHOR_END: physical_fields
+
+    // ANCHOR: hit_impulse_field
+    // A push (in N*s), that a body part of a ragdoll gets when a hitscan projectile kills its
+    // owner.
+    hit_impulse: InheritableVariable<f32>,
+    // ANCHOR_END: hit_impulse_field
 }
 
-impl ScriptTrait for Projectile {
+impl Default for Projectile {
+    fn default() -> Self {
+        Self {
+            trail: Default::default(),
+            impact_effect: Default::default(),
+            impacts: Default::default(),
+            impact_sounds: Default::default(),
+            audio_bus: "Primary".to_string().into(),
+            damage: 0.0,
+            owner: Default::default(),
+            mode: Default::default(),
+            fired: false,
+            visual_only: false,
+            sensor: Default::default(),
+            launch_speed: 30.0.into(),
+            fuse_time: 5.0.into(),
+            explode_on_impact: true.into(),
+            splash_radius: 5.0.into(),
+            splash_impulse: 20.0.into(),
+            splash_sensor: Default::default(),
+            lifetime: 0.0,
+            sensor_warning_shown: false,
+            hit_impulse: 15.0.into(),
+        }
+    }
+}
+
+impl Projectile {
+    // ANCHOR: reset
+    // Prepares the projectile to be fired again.
+    pub fn reset(&mut self) {
+        self.fired = false;
+        self.lifetime = 0.0;
+    }
+    // ANCHOR_END: reset
+
+    // ANCHOR: launch
+    fn launch(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        let Ok(rigid_body) = ctx.scene.graph.try_get_mut_of_type::<RigidBody>(ctx.handle) else {
+            Log::warn("Physical projectile must be a rigid body! The projectile is removed.");
+            ctx.plugins
+                .get_mut::<Game>()
+                .pools
+                .despawn(ctx.handle, &mut ctx.scene.graph);
+            return Ok(());
+        };
+
+        // The projectile could be used before, so it must forget its previous flight.
+        let velocity = rigid_body.look_vector().scale(*self.launch_speed);
+        rigid_body.set_lin_vel(velocity);
+        rigid_body.set_ang_vel(Vector3::zeros());
+
+        // The sensor has no mass, otherwise it would make the projectile heavy.
+        if ctx.scene.graph.try_get(self.splash_sensor).is_err() {
+            let sensor = ColliderBuilder::new(BaseBuilder::new().with_name("Splash Sensor"))
+                .with_shape(ColliderShape::ball(*self.splash_radius))
+                .with_sensor(true)
+                .with_density(Some(0.0))
+                .build(&mut ctx.scene.graph);
+            ctx.scene.graph.link_nodes(sensor.to_base(), ctx.handle);
+            self.splash_sensor = sensor;
+        }
+
+        Ok(())
+    }
+    // ANCHOR_END: launch
+
+    // ANCHOR: has_impact
+    fn has_impact(&mut self, ctx: &ScriptContext) -> Result<bool, GameError> {
+        let graph = &ctx.scene.graph;
+        let Ok(sensor) = graph.try_get(*self.sensor) else {
+            // Without the sensor the projectile touches nothing and explodes when the fuse runs
+            // out.
+            if !self.sensor_warning_shown {
+                Log::warn("The physical projectile has no sensor, it cannot explode on impact.");
+                self.sensor_warning_shown = true;
+            }
+            return Ok(false);
+        };
+
+        for intersection in sensor
+            .intersects(&graph.physics)
+            .filter(|i| i.has_any_active_contact)
+        {
+            for collider_handle in [intersection.collider1, intersection.collider2] {
+                let other_collider = graph.try_get(collider_handle)?;
+                let body = other_collider.parent();
+                // Ignore the projectile itself and the one who fired it.
+                if body != ctx.handle && body != self.owner {
+                    return Ok(true);
+                }
+            }
+        }
+
+        Ok(false)
+    }
+    // ANCHOR_END: has_impact
+
+    // ANCHOR: explode
+    fn explode(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        let center = ctx.scene.graph[ctx.handle].global_position();
+
+        if let Some(effect) = self.impact_effect.as_ref() {
+            ctx.plugins.get_mut::<Game>().pools.spawn(
+                effect,
+                center,
+                math::vector_to_quat(Vector3::y()),
+                ctx.scene,
+            );
+        }
+
+        // Explosions do not care about the surface.
+        if let Some(impact_sounds) = self.impact_sounds.as_ref() {
+            play_surface_sound(
+                impact_sounds,
+                Handle::NONE,
+                center,
+                &self.audio_bus,
+                ctx.resource_manager,
+                &mut ctx.scene.graph,
+            );
+        }
+
+        // Bodies, that touch the splash sensor, are hit by the explosion.
+        let radius = *self.splash_radius;
+        let mut victims = Vec::new();
+        if let Ok(sensor) = ctx.scene.graph.try_get(self.splash_sensor) {
+            for intersection in sensor
+                .intersects(&ctx.scene.graph.physics)
+                .filter(|i| i.has_any_active_contact)
+            {
+                for collider_handle in [intersection.collider1, intersection.collider2] {
+                    let body = ctx.scene.graph.try_get(collider_handle)?.parent();
+                    // Ignore the projectile itself, the sensor belongs to it as well.
+                    if body != ctx.handle && !victims.contains(&body) {
+                        victims.push(body);
+                    }
+                }
+            }
+        }
+
+        for victim in victims {
+            let Ok(rigid_body) = ctx.scene.graph.try_get_mut_of_type::<RigidBody>(victim) else {
+                continue;
+            };
+
+            let offset = rigid_body.global_position() - center;
+            let distance = offset.norm();
+            let falloff = (1.0 - distance / radius).clamp(0.0, 1.0);
+            let direction = offset
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_else(Vector3::y);
+            let impulse = direction.scale(*self.splash_impulse * falloff);
+
+            rigid_body.apply_impulse(impulse);
+            rigid_body.wake_up();
+
+            // Scripts of the victims could also be interested in the impulse, for example players
+            // set their velocity directly and need to know how to react.
+            let knockback = impulse.scale(1.0 / rigid_body.mass().max(f32::EPSILON));
+            ctx.message_sender.send_to_target(
+                victim,
+                DamageMessage {
+                    amount: self.damage * falloff,
+                    dealer: self.owner,
+                    knockback,
+                    hit: Some(DamageHit {
+                        collider: Handle::NONE,
+                        position: center,
+                        impulse,
+                    }),
+                },
+            );
+        }
+
+        ctx.plugins
+            .get_mut::<Game>()
+            .pools
+            .despawn(ctx.handle, &mut ctx.scene.graph);
+
+        Ok(())
+    }
+    // ANCHOR_END: explode
+
     // ANCHOR: on_start_begin
-    fn on_start(&mut self, ctx: &mut ScriptContext) -> GameResult {
+    fn fire(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        self.fired = true;
+
+        if self.mode == ProjectileMode::Physical {
+            return self.launch(ctx);
+        }
+
         let this_node = &ctx.scene.graph[ctx.handle];
         let this_node_position = this_node.global_position();
 
@@ -67,16 +371,81 @@ impl ScriptTrait for Projectile {
 
         // ANCHOR: effect_spawn
         if let Some(intersection) = intersections.first() {
-            if let Some(effect) = self.impact_effect.as_ref() {
-                effect.instantiate_at(
-                    ctx.scene,
-                    intersection.position.coords,
-                    math::vector_to_quat(intersection.normal),
-                );
+            let mut has_surface_impact = false;
+            let game = ctx.plugins.get_mut::<Game>();
+            if let Some(impacts) = self.impacts.as_ref() {
+                if let Some(impacts) = impacts.data_ref().as_loaded_ref() {
+                    has_surface_impact = spawn_impact(
+                        impacts,
+                        &mut game.decals,
+                        &mut game.pools,
+                        intersection,
+                        ctx.resource_manager,
+                        ctx.scene,
+                    );
+                }
+            }
+
+            if !has_surface_impact {
+                if let Some(effect) = self.impact_effect.as_ref() {
+                    game.pools.spawn(
+                        effect,
+                        intersection.position.coords,
+                        math::vector_to_quat(intersection.normal),
+                        ctx.scene,
+                    );
+                }
             }
         }
         // ANCHOR_END: effect_spawn
 
+        // ANCHOR: impact_sound
+        if let (Some(intersection), Some(impact_sounds)) =
+            (intersections.first(), self.impact_sounds.as_ref())
+        {
+            play_surface_sound(
+                impact_sounds,
+                intersection.collider,
+                intersection.position.coords,
+                &self.audio_bus,
+                ctx.resource_manager,
+                &mut ctx.scene.graph,
+            );
+        }
+        // ANCHOR_END: impact_sound
+
+        // The server deals the damage.
+        if self.visual_only {
+            return Ok(());
+        }
+
+        // ANCHOR: damage_dealing
+        if let Some(intersection) = intersections.first() {
+            // Colliders are attached to rigid bodies, which in their turn have Bot or Player
+            // scripts. Only the scripts that are subscribed to the damage message will receive it.
+            // The body could also be a body part of a ragdoll, so the message goes up the
+            // hierarchy to reach the owner of the ragdoll.
+            let collider = ctx.scene.graph.try_get(intersection.collider)?;
+            let direction = (intersection.position.coords - this_node_position)
+                .try_normalize(f32::EPSILON)
+                .unwrap_or_default();
+            ctx.message_sender.send_hierarchical(
+                collider.parent(),
+                RoutingStrategy::Up,
+                DamageMessage {
+                    amount: self.damage,
+                    dealer: self.owner,
+                    knockback: Default::default(),
+                    hit: Some(DamageHit {
+                        collider: intersection.collider,
+                        position: intersection.position.coords,
+                        impulse: direction.scale(*self.hit_impulse),
+                    }),
+                },
+            );
+        }
+        // ANCHOR_END: damage_dealing
+
         // ANCHOR: object_pushing
         if let Some(intersection) = intersections.first() {
             let collider = ctx.scene.graph.try_get(intersection.collider)?;
@@ -102,3 +471,36 @@ impl ScriptTrait for Projectile {
     }
     // ANCHOR_END: on_start_end
 }
+
+impl ScriptTrait for Projectile {
+    // ANCHOR: physical_update
+    fn on_update(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        // Copies of the shots are shown on network clients, while their world is frozen.
+        if self.visual_only && !self.fired {
+            return self.fire(ctx);
+        }
+
+        // Menus are opened or the server simulates the world, it is frozen here.
+        if !ctx.plugins.get::<Game>().is_simulating() {
+            return Ok(());
+        }
+
+        // `on_start` is called only once per script, while pooled projectiles are fired every
+        // time they're spawned.
+        if !self.fired {
+            return self.fire(ctx);
+        }
+
+        if self.mode != ProjectileMode::Physical {
+            return Ok(());
+        }
+
+        self.lifetime += ctx.dt;
+        if self.lifetime >= *self.fuse_time || (*self.explode_on_impact && self.has_impact(ctx)?) {
+            self.explode(ctx)?;
+        }
+
+        Ok(())
+    }
+    // ANCHOR_END: physical_update
+}
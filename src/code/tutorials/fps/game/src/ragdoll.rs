@@ -0,0 +1,324 @@
+use crate::surface::FLESH_SURFACE;
+use fyrox::{
+    core::{
+        algebra::{UnitQuaternion, Vector3},
+        pool::Handle,
+        reflect::prelude::*,
+        visitor::prelude::*,
+    },
+    graph::SceneGraph,
+    scene::{
+        base::BaseBuilder,
+        collider::{BitMask, Collider, ColliderBuilder, ColliderShape, InteractionGroups},
+        graph::Graph,
+        joint::{BallJoint, JointBuilder, JointParams},
+        node::Node,
+        ragdoll::{Limb, Ragdoll, RagdollBuilder},
+        rigidbody::{RigidBody, RigidBodyBuilder, RigidBodyType},
+        transform::TransformBuilder,
+    },
+};
+
+// Body parts of ragdolls are in this collision group. Physical capsules of the characters ignore
+// the group, otherwise the ragdoll and the capsule would push each other and the ragdoll would
+// "explode".
+pub const RAGDOLL_GROUP: u32 = 1 << 15;
+
+// ANCHOR: ragdoll_settings
+#[derive(Visit, Reflect, Debug, Clone)]
+#[visit(optional)]
+pub struct RagdollSettings {
+    // Total mass of the ragdoll, it is distributed between the body parts by their length.
+    total_mass: f32,
+
+    // Radius of a body part relative to its length.
+    thickness: f32,
+
+    // Bones shorter than this (in meters) do not get their own body parts, fingers for example.
+    min_limb_length: f32,
+
+    // How much (in degrees) the body parts could rotate relative to their parents on each axis.
+    joint_limit: f32,
+
+    // Continuous collision detection prevents fast body parts from falling through the floor.
+    use_ccd: bool,
+}
+
+impl Default for RagdollSettings {
+    fn default() -> Self {
+        Self {
+            total_mass: 70.0,
+            thickness: 0.25,
+            min_limb_length: 0.05,
+            joint_limit: 45.0,
+            use_ccd: true,
+        }
+    }
+}
+// ANCHOR_END: ragdoll_settings
+
+// A body part goes from a bone to the average position of its children.
+fn limb_end(graph: &Graph, bone: Handle<Node>) -> Option<Vector3<f32>> {
+    let children = graph[bone]
+        .children()
+        .iter()
+        .filter_map(|child| graph.try_get(*child).ok())
+        .map(|child| child.global_position())
+        .collect::<Vec<_>>();
+    if children.is_empty() {
+        return None;
+    }
+    Some(children.iter().sum::<Vector3<f32>>() / children.len() as f32)
+}
+
+fn skeleton_length(graph: &Graph, bone: Handle<Node>, settings: &RagdollSettings) -> f32 {
+    let length = limb_end(graph, bone)
+        .map(|end| end.metric_distance(&graph[bone].global_position()))
+        .filter(|length| *length >= settings.min_limb_length)
+        .unwrap_or_default();
+    length
+        + graph[bone]
+            .children()
+            .iter()
+            .map(|child| skeleton_length(graph, *child, settings))
+            .sum::<f32>()
+}
+
+// ANCHOR: build_limb
+fn build_limb(
+    graph: &mut Graph,
+    bone: Handle<Node>,
+    mass_per_meter: f32,
+    settings: &RagdollSettings,
+    bodies: &mut Vec<Handle<Node>>,
+) -> Option<Limb> {
+    let start = graph.try_get(bone).ok()?.global_position();
+    let end = limb_end(graph, bone)?;
+    let segment = end - start;
+    let length = segment.norm();
+    if length < settings.min_limb_length {
+        return None;
+    }
+
+    // The capsule is aligned with the bone, its Y axis goes from the bone to its children.
+    let radius = length * settings.thickness;
+    let collider = ColliderBuilder::new(BaseBuilder::new().with_tag(FLESH_SURFACE.to_string()))
+        .with_shape(ColliderShape::capsule_y(
+            (length * 0.5 - radius).max(0.0),
+            radius,
+        ))
+        .with_collision_groups(InteractionGroups::new(
+            BitMask(RAGDOLL_GROUP),
+            BitMask(u32::MAX),
+        ))
+        .build(graph);
+
+    let rotation = UnitQuaternion::rotation_between(&Vector3::y(), &segment).unwrap_or_default();
+    let body = RigidBodyBuilder::new(
+        BaseBuilder::new()
+            .with_name(format!("{} Limb", graph[bone].name()))
+            .with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(start + segment.scale(0.5))
+                    .with_local_rotation(rotation)
+                    .build(),
+            )
+            .with_child(collider),
+    )
+    .with_body_type(RigidBodyType::Dynamic)
+    .with_mass(length * mass_per_meter)
+    .with_ccd_enabled(settings.use_ccd)
+    .with_can_sleep(false)
+    .build(graph)
+    .to_base();
+    bodies.push(body);
+
+    let children = graph[bone].children().to_vec();
+    let mut limbs = Vec::new();
+    for child in children {
+        let Some(child_limb) = build_limb(graph, child, mass_per_meter, settings, bodies) else {
+            continue;
+        };
+
+        // Connect the child to this body part at the joint of the bones.
+        let limit = settings.joint_limit.to_radians();
+        let joint_position = graph[child].global_position();
+        let joint = JointBuilder::new(
+            BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(joint_position)
+                    .build(),
+            ),
+        )
+        .with_body1(body)
+        .with_body2(child_limb.physical_bone)
+        .with_contacts_enabled(false)
+        .with_params(JointParams::BallJoint(BallJoint {
+            x_limits_enabled: true,
+            x_limits_angles: -limit..limit,
+            y_limits_enabled: true,
+            y_limits_angles: -limit..limit,
+            z_limits_enabled: true,
+            z_limits_angles: -limit..limit,
+        }))
+        .build(graph)
+        .to_base();
+        bodies.push(joint);
+
+        limbs.push(child_limb);
+    }
+
+    Some(Limb {
+        bone,
+        physical_bone: body,
+        children: limbs,
+    })
+}
+// ANCHOR_END: build_limb
+
+// ANCHOR: build_ragdoll
+// Generates body parts (rigid bodies with capsule colliders) for the bones of a skeleton, that
+// are connected with ball joints. The ragdoll is attached to the character and starts inactive:
+// the body parts follow the animated bones until the ragdoll is activated.
+pub fn build_ragdoll(
+    graph: &mut Graph,
+    character: Handle<Node>,
+    root_bone: Handle<Node>,
+    settings: &RagdollSettings,
+) -> Handle<Node> {
+    // The character could be spawned in this frame, its bones do not have valid global positions
+    // yet.
+    graph.update_hierarchical_data();
+
+    let total_length = skeleton_length(graph, root_bone, settings);
+    let mass_per_meter = settings.total_mass / total_length.max(f32::EPSILON);
+
+    let mut nodes = Vec::new();
+    let Some(root_limb) = build_limb(graph, root_bone, mass_per_meter, settings, &mut nodes) else {
+        return Handle::NONE;
+    };
+
+    let ragdoll = RagdollBuilder::new(BaseBuilder::new().with_name("Ragdoll"))
+        .with_root_limb(root_limb)
+        .with_active(false)
+        .build(graph);
+
+    // Body parts are created in world coordinates, keep them in place when attaching them. Global
+    // transforms of the new nodes are calculated only by the hierarchy update.
+    graph.update_hierarchical_data();
+    for node in nodes {
+        graph.link_nodes_keep_global_transform(node, ragdoll);
+    }
+    graph.link_nodes_keep_global_transform(ragdoll, character);
+
+    // The physical capsule of the character must not collide with the body parts.
+    for child in graph[character].children().to_vec() {
+        if let Ok(collider) = graph.try_get_mut_of_type::<Collider>(child) {
+            let mut groups = collider.collision_groups();
+            groups.filter = BitMask(groups.filter.0 & !RAGDOLL_GROUP);
+            collider.set_collision_groups(groups);
+        }
+    }
+
+    ragdoll
+}
+// ANCHOR_END: build_ragdoll
+
+// ANCHOR: capsule_groups
+// While the ragdoll is active, the physical capsule of the character would stand where the
+// character died, blocking others, shots and splash damage. It is moved out of every collision
+// group then. Returns the previous groups of the capsule, so they could be restored later.
+pub fn set_capsule_groups(
+    character: Handle<Node>,
+    groups: InteractionGroups,
+    graph: &mut Graph,
+) -> Option<InteractionGroups> {
+    let children = graph
+        .try_get(character)
+        .map(|character| character.children().to_vec())
+        .unwrap_or_default();
+    let mut previous = None;
+    for child in children {
+        if let Ok(collider) = graph.try_get_mut_of_type::<Collider>(child) {
+            previous.get_or_insert(collider.collision_groups());
+            collider.set_collision_groups(groups);
+        }
+    }
+    previous
+}
+
+pub fn no_collision_groups() -> InteractionGroups {
+    InteractionGroups::new(BitMask(0), BitMask(0))
+}
+
+// Gives the tag to the capsule of the character, unless the prefab has its own tag for it.
+pub fn set_capsule_tag(character: Handle<Node>, tag: &str, graph: &mut Graph) {
+    let children = graph
+        .try_get(character)
+        .map(|character| character.children().to_vec())
+        .unwrap_or_default();
+    for child in children {
+        if let Ok(collider) = graph.try_get_mut_of_type::<Collider>(child) {
+            if collider.tag().is_empty() {
+                collider.set_tag(tag.to_string());
+            }
+        }
+    }
+}
+// ANCHOR_END: capsule_groups
+
+// ANCHOR: limb_bodies
+// Returns pairs of bones and body parts of the ragdoll.
+pub fn limbs(ragdoll: &Ragdoll) -> Vec<(Handle<Node>, Handle<Node>)> {
+    fn collect(limb: &Limb, limbs: &mut Vec<(Handle<Node>, Handle<Node>)>) {
+        limbs.push((limb.bone, limb.physical_bone));
+        for child in limb.children.iter() {
+            collect(child, limbs);
+        }
+    }
+
+    let mut limbs = Vec::new();
+    collect(ragdoll.root_limb(), &mut limbs);
+    limbs
+}
+
+// Pushes the body part, that was hit, or the closest one if the hit collider is not a part of the
+// ragdoll (the physical capsule of the character, for example).
+pub fn push_limb(
+    ragdoll: Handle<Node>,
+    collider: Handle<Node>,
+    position: Vector3<f32>,
+    impulse: Vector3<f32>,
+    graph: &mut Graph,
+) {
+    let Ok(ragdoll) = graph.try_get_of_type::<Ragdoll>(ragdoll) else {
+        return;
+    };
+    let bodies = limbs(ragdoll)
+        .into_iter()
+        .map(|(_, body)| body)
+        .collect::<Vec<_>>();
+
+    let hit_body = graph
+        .try_get(collider)
+        .map(|collider| collider.parent())
+        .ok()
+        .filter(|body| bodies.contains(body));
+    let body = hit_body.or_else(|| {
+        bodies
+            .iter()
+            .filter_map(|body| graph.try_get(*body).ok().map(|node| (*body, node)))
+            .min_by(|(_, a), (_, b)| {
+                a.global_position()
+                    .metric_distance(&position)
+                    .total_cmp(&b.global_position().metric_distance(&position))
+            })
+            .map(|(body, _)| body)
+    });
+
+    if let Some(body) = body.and_then(|body| graph.try_get_mut_of_type::<RigidBody>(body).ok()) {
+        body.apply_impulse_at_point(impulse, position);
+        body.wake_up();
+    }
+}
+// ANCHOR_END: limb_bodies
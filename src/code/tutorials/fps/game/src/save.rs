@@ -0,0 +1,173 @@
+use crate::Game;
+use fyrox::{
+    core::{log::Log, visitor::prelude::*},
+    plugin::{error::GameResult, PluginContext},
+    scene::Scene,
+};
+use std::path::Path;
+
+pub const SAVE_PATH: &str = "save.rgs";
+
+impl Game {
+    // ANCHOR: save_game
+    // The scene is saved together with its scripts, so everything that scripts store in visitable
+    // fields (AI targets, timers, ammo, health, etc.) is saved as well. The state of the plugin is
+    // stored next to the scene.
+    pub fn save_game(&mut self, path: &Path, ctx: &mut PluginContext) -> GameResult {
+        let scene = ctx.scenes.try_get_mut(self.flow.scene())?;
+        self.save_scene(scene, path)?;
+
+        Log::info(format!("The game was saved to {}", path.display()));
+
+        Ok(())
+    }
+
+    pub(crate) fn save_scene(&mut self, scene: &mut Scene, path: &Path) -> GameResult {
+        let mut visitor = Visitor::new();
+        scene.save("Scene", &mut visitor)?;
+        self.waves.visit("Waves", &mut visitor)?;
+        visitor.save_binary_to_file(path)?;
+        Ok(())
+    }
+    // ANCHOR_END: save_game
+
+    // ANCHOR: load_game
+    pub fn load_game(&mut self, path: &Path, ctx: &mut PluginContext) {
+        // The saved scene is loaded like any other level, the rest of the state is restored when
+        // the scene is ready (see `restore_saved_state`).
+        self.loading_save = Some(path.to_path_buf());
+        self.flow.load_level::<Game>(&path.to_string_lossy(), ctx);
+    }
+
+    pub(crate) fn restore_saved_state(&mut self, path: &Path) -> GameResult {
+        let data = std::fs::read(path).map_err(VisitError::from)?;
+        let mut visitor = Visitor::load_from_memory(&data)?;
+        self.waves.visit("Waves", &mut visitor)?;
+        Ok(())
+    }
+    // ANCHOR_END: load_game
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        bot::Bot,
+        player::Player,
+        wave::{Wave, WaveGroup, WaveManager},
+        weapon::Weapon,
+        Game,
+    };
+    use fyrox::{
+        core::{pool::Handle, reflect::prelude::*, visitor::prelude::*},
+        engine::SerializationContext,
+        scene::{base::BaseBuilder, node::Node, pivot::PivotBuilder, Scene},
+        script::ScriptTrait,
+    };
+    use std::sync::Arc;
+
+    fn test_waves() -> Vec<Wave> {
+        vec![Wave {
+            delay: 0.0,
+            spawn_interval: 1.0,
+            groups: vec![WaveGroup {
+                prefab: "data/models/zombie/zombie.rgs".into(),
+                count: 3,
+            }],
+        }]
+    }
+
+    // Compares the values field by field, so a failure names the field. Values without fields
+    // (numbers, handles, strings, collections) are compared by their debug output.
+    fn assert_reflect_eq(path: &str, expected: &dyn Reflect, actual: &dyn Reflect) {
+        let mut has_fields = false;
+        expected.fields_ref(&mut |expected_fields| {
+            actual.fields_ref(&mut |actual_fields| {
+                assert_eq!(expected_fields.len(), actual_fields.len(), "{path}");
+                for (expected, actual) in expected_fields.iter().zip(actual_fields) {
+                    assert_reflect_eq(
+                        &format!("{path}.{}", expected.metadata.name),
+                        expected.value.field_value_as_reflect(),
+                        actual.value.field_value_as_reflect(),
+                    );
+                }
+                has_fields = !expected_fields.is_empty();
+            })
+        });
+        if !has_fields {
+            assert_eq!(format!("{expected:?}"), format!("{actual:?}"), "{path}");
+        }
+    }
+
+    fn script<'a, T: ScriptTrait>(scene: &'a Scene, node: Handle<Node>) -> &'a T {
+        scene.graph[node].try_get_script::<T>().unwrap()
+    }
+
+    // Saves the game in the middle of a fight, loads it back the same way as the game does and
+    // checks that the bots, the player and its weapon continue from the same state.
+    #[test]
+    fn saved_game_round_trip() {
+        let mut scene = Scene::new();
+        let spawn_point = PivotBuilder::new(BaseBuilder::new()).build(&mut scene.graph);
+        let player = PivotBuilder::new(BaseBuilder::new()).build(&mut scene.graph);
+        let weapon = PivotBuilder::new(BaseBuilder::new()).build(&mut scene.graph);
+        let bot = PivotBuilder::new(BaseBuilder::new()).build(&mut scene.graph);
+        scene.graph[player].add_script(Player::mid_fight(weapon));
+        scene.graph[weapon].add_script(Weapon::mid_fight(player));
+        scene.graph[bot].add_script(Bot::mid_fight(player));
+
+        // Start the wave and spawn its first enemy.
+        let mut game = Game::default();
+        game.waves = WaveManager::new(test_waves());
+        game.waves.add_spawn_point(spawn_point);
+        game.waves.update(0.1, &scene.graph);
+        assert_eq!(game.waves.update(0.1, &scene.graph).len(), 1);
+        game.waves.finish_spawn();
+        game.waves.add_enemy(bot);
+
+        let path = std::env::temp_dir().join("fps_saved_game_round_trip.rgs");
+        game.save_scene(&mut scene, &path).unwrap();
+
+        // The saved scene is loaded like a level, then the plugin restores its own state.
+        let data = std::fs::read(&path).unwrap();
+        let serialization_context = SerializationContext::new();
+        serialization_context.script_constructors.add::<Bot>("Bot");
+        serialization_context
+            .script_constructors
+            .add::<Player>("Player");
+        serialization_context
+            .script_constructors
+            .add::<Weapon>("Weapon");
+        let mut visitor = Visitor::load_from_memory(&data).unwrap();
+        visitor.blackboard.register(Arc::new(serialization_context));
+        let mut loaded_scene = Scene::new();
+        loaded_scene.visit("Scene", &mut visitor).unwrap();
+
+        let mut loaded_game = Game::default();
+        loaded_game.waves = WaveManager::new(test_waves());
+        loaded_game.restore_saved_state(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let (expected, actual) = (
+            script::<Bot>(&scene, bot),
+            script::<Bot>(&loaded_scene, bot),
+        );
+        assert_reflect_eq("Bot", expected, actual);
+        expected.assert_same_runtime_state(actual);
+
+        let (expected, actual) = (
+            script::<Player>(&scene, player),
+            script::<Player>(&loaded_scene, player),
+        );
+        assert_reflect_eq("Player", expected, actual);
+        expected.assert_same_runtime_state(actual);
+
+        let (expected, actual) = (
+            script::<Weapon>(&scene, weapon),
+            script::<Weapon>(&loaded_scene, weapon),
+        );
+        assert_reflect_eq("Weapon", expected, actual);
+        expected.assert_same_runtime_state(actual);
+
+        assert_eq!(game.waves.state(), loaded_game.waves.state());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::surface::surface_of;
+use common::resource::{save_ron, RonLoader};
+use fyrox::{
+    asset::{manager::ResourceManager, Resource, ResourceData},
+    core::{
+        algebra::Vector3,
+        pool::Handle,
+        rand::{thread_rng, Rng},
+        reflect::prelude::*,
+        type_traits::prelude::*,
+        uuid::Uuid,
+        visitor::prelude::*,
+        TypeUuidProvider,
+    },
+    graph::SceneGraph,
+    scene::{
+        base::BaseBuilder,
+        graph::Graph,
+        node::Node,
+        sound::{SoundBuffer, SoundBufferResource, SoundBuilder, Status},
+        transform::TransformBuilder,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+// ANCHOR: sound_table
+// A set of sounds for each kind of surface. Sound tables are stored in RON files, so designers could
+// tweak them without touching the code.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Visit, Reflect, TypeUuidProvider)]
+#[type_uuid(id = "6a0e1f7d-3c2b-4d8e-9f45-1b7c8e2a9d30")]
+pub struct SoundTable {
+    // Surface, that is used when the surface of a collider is unknown.
+    pub default_surface: String,
+
+    // Surfaces of colliders without tags, by the names of the colliders.
+    #[serde(default)]
+    pub collider_names: HashMap<String, String>,
+
+    // A random sound from the list is picked every time.
+    pub surfaces: HashMap<String, Vec<PathBuf>>,
+}
+
+impl SoundTable {
+    pub fn surface_of<'a>(&'a self, collider: &'a Node) -> &'a str {
+        surface_of(collider, &self.collider_names, &self.default_surface)
+    }
+
+    pub fn pick(&self, surface: &str) -> Option<&Path> {
+        let sounds = self
+            .surfaces
+            .get(surface)
+            .or_else(|| self.surfaces.get(&self.default_surface))?;
+        if sounds.is_empty() {
+            return None;
+        }
+        Some(&sounds[thread_rng().gen_range(0..sounds.len())])
+    }
+}
+
+impl ResourceData for SoundTable {
+    fn type_uuid(&self) -> Uuid {
+        <Self as TypeUuidProvider>::type_uuid()
+    }
+
+    fn save(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        save_ron(self, path)
+    }
+
+    fn can_be_saved(&self) -> bool {
+        true
+    }
+
+    fn try_clone_box(&self) -> Option<Box<dyn ResourceData>> {
+        Some(Box::new(self.clone()))
+    }
+}
+
+pub fn sound_table_loader() -> RonLoader<SoundTable> {
+    RonLoader::new(&["sound_table"])
+}
+// ANCHOR_END: sound_table
+
+// ANCHOR: play_sound
+// Creates a sound node, that plays once at the given position and then removes itself.
+pub fn play_sound(
+    buffer: SoundBufferResource,
+    position: Vector3<f32>,
+    audio_bus: &str,
+    graph: &mut Graph,
+) {
+    SoundBuilder::new(
+        BaseBuilder::new().with_local_transform(
+            TransformBuilder::new()
+                .with_local_position(position)
+                .build(),
+        ),
+    )
+    .with_buffer(Some(buffer))
+    .with_audio_bus(audio_bus.to_string())
+    .with_status(Status::Playing)
+    .with_play_once(true)
+    .build(graph);
+}
+
+// Plays a random sound of the surface of the given collider.
+pub fn play_surface_sound(
+    table: &Resource<SoundTable>,
+    collider: Handle<Node>,
+    position: Vector3<f32>,
+    audio_bus: &str,
+    resource_manager: &ResourceManager,
+    graph: &mut Graph,
+) {
+    let path = {
+        let table = table.data_ref();
+        let Some(table) = table.as_loaded_ref() else {
+            return;
+        };
+        let surface = graph
+            .try_get(collider)
+            .map(|collider| table.surface_of(collider).to_string())
+            .unwrap_or_else(|_| table.default_surface.clone());
+        table.pick(&surface).map(Path::to_path_buf)
+    };
+
+    if let Some(path) = path {
+        let buffer = resource_manager.request::<SoundBuffer>(path);
+        play_sound(buffer, position, audio_bus, graph);
+    }
+}
+// ANCHOR_END: play_sound
@@ -0,0 +1,46 @@
+use crate::Game;
+use fyrox::plugin::error::GameResult;
+use fyrox::{
+    core::{
+        pool::Handle, reflect::prelude::*, type_traits::prelude::*, variable::InheritableVariable,
+        visitor::prelude::*,
+    },
+    scene::navmesh::NavigationalMesh,
+    script::{ScriptContext, ScriptDeinitContext, ScriptTrait},
+};
+
+// ANCHOR: spawn_point
+// Marks a place where the enemies of the waves appear. Spawn points register themselves in the
+// game plugin, so it knows where to put new enemies.
+#[derive(Visit, Reflect, Default, Debug, Clone, TypeUuidProvider, ComponentProvider)]
+#[type_uuid(id = "0d7b5c1e-3f2a-4e68-b9d4-6a8c2e1f7b35")]
+#[visit(optional)]
+pub struct SpawnPoint {
+    // Navigational mesh, that will be used by the bots spawned at this point.
+    navmesh: InheritableVariable<Handle<NavigationalMesh>>,
+}
+
+impl SpawnPoint {
+    pub fn navmesh(&self) -> Handle<NavigationalMesh> {
+        *self.navmesh
+    }
+}
+
+impl ScriptTrait for SpawnPoint {
+    fn on_start(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        ctx.plugins
+            .get_mut::<Game>()
+            .waves
+            .add_spawn_point(ctx.handle);
+        Ok(())
+    }
+
+    fn on_deinit(&mut self, ctx: &mut ScriptDeinitContext) -> GameResult {
+        ctx.plugins
+            .get_mut::<Game>()
+            .waves
+            .remove_spawn_point(ctx.node_handle);
+        Ok(())
+    }
+}
+// ANCHOR_END: spawn_point
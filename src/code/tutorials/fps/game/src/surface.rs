@@ -0,0 +1,23 @@
+use fyrox::scene::node::Node;
+use std::collections::HashMap;
+
+// Characters and their ragdolls are tagged with it, so they bleed instead of leaving holes.
+pub const FLESH_SURFACE: &str = "Flesh";
+
+// ANCHOR: surface_of
+// Surface of a collider (metal, wood, flesh, etc.) is defined by its tag, that works like a physics
+// material. Colliders without tags could be mapped to surfaces by their names.
+pub fn surface_of<'a>(
+    collider: &'a Node,
+    collider_names: &'a HashMap<String, String>,
+    default_surface: &'a str,
+) -> &'a str {
+    if !collider.tag().is_empty() {
+        return collider.tag();
+    }
+    collider_names
+        .get(collider.name())
+        .map(String::as_str)
+        .unwrap_or(default_surface)
+}
+// ANCHOR_END: surface_of
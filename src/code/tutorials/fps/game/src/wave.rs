@@ -0,0 +1,299 @@
+use crate::bot::Bot;
+use fyrox::{
+    core::{log::Log, pool::Handle, visitor::prelude::*},
+    scene::{graph::Graph, node::Node},
+};
+use serde::Deserialize;
+use std::{
+    collections::VecDeque,
+    path::{Path, PathBuf},
+};
+
+// ANCHOR: wave_data
+// A number of enemies of the same kind.
+#[derive(Deserialize, Debug, Clone)]
+pub struct WaveGroup {
+    pub prefab: PathBuf,
+    pub count: u32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Wave {
+    // Time (in seconds) between the end of the previous wave and the beginning of this one.
+    #[serde(default)]
+    pub delay: f32,
+    // Time (in seconds) between two spawns.
+    pub spawn_interval: f32,
+    pub groups: Vec<WaveGroup>,
+}
+
+impl Wave {
+    // Every enemy of the wave in the order of appearance.
+    pub fn prefabs(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.groups
+            .iter()
+            .flat_map(|group| (0..group.count).map(|_| group.prefab.clone()))
+    }
+
+    pub fn enemy_count(&self) -> usize {
+        self.groups.iter().map(|group| group.count as usize).sum()
+    }
+}
+
+pub fn load_waves(path: impl AsRef<Path>) -> Vec<Wave> {
+    let path = path.as_ref();
+    let result = std::fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|text| ron::from_str(&text).map_err(|err| err.to_string()));
+
+    match result {
+        Ok(waves) => waves,
+        Err(err) => {
+            Log::err(format!(
+                "Unable to load waves from {}: {err}",
+                path.display()
+            ));
+            Default::default()
+        }
+    }
+}
+// ANCHOR_END: wave_data
+
+// ANCHOR: round_state
+#[derive(Visit, Debug, Clone, Copy, PartialEq, Default)]
+pub enum RoundPhase {
+    // There are no waves.
+    #[default]
+    Idle,
+    // Waiting for the next wave.
+    Intermission {
+        time_left: f32,
+    },
+    // The enemies of the current wave are spawning or still alive.
+    InProgress,
+    // Every wave is defeated.
+    Completed,
+}
+
+// A snapshot of the round, that could be shown in the UI.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RoundState {
+    pub phase: RoundPhase,
+    // Index of the current (or the upcoming) wave, starting from zero.
+    pub wave: usize,
+    pub total_waves: usize,
+    pub alive_enemies: usize,
+    // Enemies of the current wave, that haven't appeared yet.
+    pub remaining_spawns: usize,
+}
+// ANCHOR_END: round_state
+
+// A request to put an enemy prefab at the spawn point.
+#[derive(Debug, Clone)]
+pub struct SpawnRequest {
+    pub prefab: PathBuf,
+    pub spawn_point: Handle<Node>,
+}
+
+// ANCHOR: wave_manager
+#[derive(Default, Debug, Clone)]
+pub struct WaveManager {
+    waves: Vec<Wave>,
+    current_wave: usize,
+    phase: RoundPhase,
+    // Prefabs of the current wave, that are waiting for their turn.
+    spawn_queue: VecDeque<PathBuf>,
+    spawn_timer: f32,
+    // Prefabs, that are still loading. The wave cannot end until they're spawned.
+    spawns_in_flight: usize,
+    enemies: Vec<Handle<Node>>,
+    spawn_points: Vec<Handle<Node>>,
+    next_spawn_point: usize,
+    // The progress of the round could be loaded before the waves (hot reloading), the spawn queue
+    // is restored from the number of remaining spawns when the waves are set.
+    pending_spawns: Option<usize>,
+}
+
+impl WaveManager {
+    pub fn new(waves: Vec<Wave>) -> Self {
+        let phase = match waves.first() {
+            Some(wave) => RoundPhase::Intermission {
+                time_left: wave.delay,
+            },
+            None => RoundPhase::Idle,
+        };
+
+        Self {
+            waves,
+            phase,
+            ..Default::default()
+        }
+    }
+
+    // Waves come from the config, the progress of the round is kept.
+    pub fn set_waves(&mut self, waves: Vec<Wave>) {
+        self.waves = waves;
+        if let Some(remaining_spawns) = self.pending_spawns.take() {
+            self.restore_spawn_queue(remaining_spawns);
+        }
+    }
+
+    // The spawn queue is always the tail of the current wave.
+    fn restore_spawn_queue(&mut self, remaining_spawns: usize) {
+        self.spawn_queue = match self.waves.get(self.current_wave) {
+            Some(wave) if self.phase == RoundPhase::InProgress => {
+                let skip = wave.enemy_count().saturating_sub(remaining_spawns);
+                wave.prefabs().skip(skip).collect()
+            }
+            _ => Default::default(),
+        };
+    }
+
+    // Starts the round over, spawn points are kept.
+    pub fn restart(&mut self) {
+        let spawn_points = std::mem::take(&mut self.spawn_points);
+        *self = Self {
+            spawn_points,
+            ..Self::new(std::mem::take(&mut self.waves))
+        };
+    }
+
+    pub fn add_spawn_point(&mut self, spawn_point: Handle<Node>) {
+        if !self.spawn_points.contains(&spawn_point) {
+            self.spawn_points.push(spawn_point);
+        }
+    }
+
+    pub fn remove_spawn_point(&mut self, spawn_point: Handle<Node>) {
+        self.spawn_points.retain(|h| *h != spawn_point);
+    }
+
+    pub fn spawn_points(&self) -> &[Handle<Node>] {
+        &self.spawn_points
+    }
+
+    pub fn clear_spawn_points(&mut self) {
+        self.spawn_points.clear();
+    }
+
+    // Must be called when a spawn request is processed, even if it failed.
+    pub fn finish_spawn(&mut self) {
+        self.spawns_in_flight = self.spawns_in_flight.saturating_sub(1);
+    }
+
+    pub fn add_enemy(&mut self, enemy: Handle<Node>) {
+        self.enemies.push(enemy);
+    }
+
+    pub fn state(&self) -> RoundState {
+        RoundState {
+            phase: self.phase,
+            wave: self.current_wave,
+            total_waves: self.waves.len(),
+            alive_enemies: self.enemies.len() + self.spawns_in_flight,
+            remaining_spawns: self.spawn_queue.len(),
+        }
+    }
+
+    fn is_alive(enemy: Handle<Node>, graph: &Graph) -> bool {
+        match graph.try_get(enemy) {
+            Ok(node) => node
+                .try_get_script::<Bot>()
+                .is_none_or(|bot| !bot.health().is_dead()),
+            Err(_) => false,
+        }
+    }
+
+    fn start_wave(&mut self) {
+        let Some(wave) = self.waves.get(self.current_wave) else {
+            self.phase = RoundPhase::Completed;
+            return;
+        };
+
+        self.spawn_queue = wave.prefabs().collect();
+        self.spawn_timer = 0.0;
+        self.phase = RoundPhase::InProgress;
+    }
+
+    // Advances the round and returns the enemies, that should be spawned on this frame.
+    pub fn update(&mut self, dt: f32, graph: &Graph) -> Vec<SpawnRequest> {
+        self.enemies.retain(|enemy| Self::is_alive(*enemy, graph));
+
+        let mut requests = Vec::new();
+        match self.phase {
+            RoundPhase::Intermission { time_left } => {
+                let time_left = time_left - dt;
+                if time_left <= 0.0 {
+                    self.start_wave();
+                } else {
+                    self.phase = RoundPhase::Intermission { time_left };
+                }
+            }
+            RoundPhase::InProgress => {
+                self.spawn_timer -= dt;
+                // Enemies wait in the queue if there are no spawn points yet.
+                if self.spawn_timer <= 0.0 && !self.spawn_points.is_empty() {
+                    if let Some(prefab) = self.spawn_queue.pop_front() {
+                        let index = self.next_spawn_point % self.spawn_points.len();
+                        self.next_spawn_point = index + 1;
+                        self.spawns_in_flight += 1;
+                        self.spawn_timer = self.waves[self.current_wave].spawn_interval;
+                        requests.push(SpawnRequest {
+                            prefab,
+                            spawn_point: self.spawn_points[index],
+                        });
+                    }
+                }
+
+                if self.spawn_queue.is_empty()
+                    && self.spawns_in_flight == 0
+                    && self.enemies.is_empty()
+                {
+                    self.current_wave += 1;
+                    self.phase = match self.waves.get(self.current_wave) {
+                        Some(wave) => RoundPhase::Intermission {
+                            time_left: wave.delay,
+                        },
+                        None => RoundPhase::Completed,
+                    };
+                }
+            }
+            RoundPhase::Idle | RoundPhase::Completed => (),
+        }
+
+        requests
+    }
+}
+// ANCHOR_END: wave_manager
+
+// ANCHOR: wave_manager_visit
+// Waves come from the config, so only the progress of the round is saved. The spawn queue is always
+// the tail of the current wave, thus it could be restored from the number of remaining spawns.
+// After hot reloading the waves are set later, see `set_waves`.
+impl Visit for WaveManager {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        let mut region = visitor.enter_region(name)?;
+
+        self.current_wave.visit("CurrentWave", &mut region)?;
+        self.phase.visit("Phase", &mut region)?;
+        self.spawn_timer.visit("SpawnTimer", &mut region)?;
+        self.enemies.visit("Enemies", &mut region)?;
+
+        // Prefabs, that were loading, are spawned again.
+        let mut remaining_spawns = (self.spawn_queue.len() + self.spawns_in_flight) as u32;
+        remaining_spawns.visit("RemainingSpawns", &mut region)?;
+
+        if region.is_reading() {
+            self.spawns_in_flight = 0;
+            if self.waves.is_empty() {
+                self.spawn_queue.clear();
+                self.pending_spawns = Some(remaining_spawns as usize);
+            } else {
+                self.restore_spawn_queue(remaining_spawns as usize);
+            }
+        }
+
+        Ok(())
+    }
+}
+// ANCHOR_END: wave_manager_visit
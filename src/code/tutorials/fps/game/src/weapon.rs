@@ -1,16 +1,29 @@
+use crate::{
+    bot::NoiseMessage,
+    projectile::{Projectile, ProjectileMode},
+    sound::play_sound,
+    Game,
+};
 use fyrox::graph::SceneGraph;
 use fyrox::plugin::error::GameResult;
 use fyrox::{
     core::{
-        math, pool::Handle, reflect::prelude::*, type_traits::prelude::*,
-        variable::InheritableVariable, visitor::prelude::*,
+        algebra::{UnitQuaternion, UnitVector3, Vector2, Vector3},
+        log::Log,
+        math,
+        pool::Handle,
+        rand::{thread_rng, Rng},
+        reflect::prelude::*,
+        type_traits::prelude::*,
+        variable::InheritableVariable,
+        visitor::prelude::*,
     },
-    resource::{model::ModelResource, model::ModelResourceExtension},
-    scene::{animation::AnimationPlayer, node::Node},
+    resource::model::ModelResource,
+    scene::{animation::AnimationPlayer, graph::Graph, node::Node, sound::SoundBufferResource},
     script::{ScriptContext, ScriptMessageContext, ScriptMessagePayload, ScriptTrait},
 };
 
-#[derive(Visit, Reflect, Default, Debug, Clone, TypeUuidProvider, ComponentProvider)]
+#[derive(Visit, Reflect, Debug, Clone, TypeUuidProvider, ComponentProvider)]
 #[type_uuid(id = "e8adde73-1e96-471c-8531-5c0d16f0c29a")]
 #[visit(optional)]
 pub struct Weapon {
@@ -18,6 +31,17 @@ pub struct Weapon {
     projectile: InheritableVariable<Option<ModelResource>>,
     // ANCHOR_END: projectile_field
 
+    // ANCHOR: pool_size_field
+    // Number of projectiles, that are created beforehand. The pool grows if it's not enough.
+    projectile_pool_size: InheritableVariable<usize>,
+    // ANCHOR_END: pool_size_field
+
+    // ANCHOR: projectile_mode_field
+    // Hitscan weapons hit instantly, physical ones (rocket launchers, grenade launchers) launch
+    // rigid bodies, that explode.
+    projectile_mode: InheritableVariable<ProjectileMode>,
+    // ANCHOR_END: projectile_mode_field
+
     // ANCHOR: shot_point
     shot_point: InheritableVariable<Handle<Node>>,
     // ANCHOR_END: shot_point
@@ -32,27 +56,374 @@ pub struct Weapon {
     // ANCHOR: animation_player
     animation_player: InheritableVariable<Handle<AnimationPlayer>>,
     // ANCHOR_END: animation_player
+
+    // ANCHOR: damage_field
+    damage: InheritableVariable<f32>,
+
+    // A node that holds the weapon, it is used to tell the victims who attacked them.
+    #[reflect(hidden)]
+    owner: Handle<Node>,
+    // ANCHOR_END: damage_field
+
+    // ANCHOR: ammo_fields
+    magazine_size: InheritableVariable<u32>,
+
+    // Amount of ammo in the current magazine.
+    loaded_ammo: InheritableVariable<u32>,
+
+    // Amount of ammo that can be loaded in the magazine on reload.
+    reserve_ammo: InheritableVariable<u32>,
+
+    reload_time: InheritableVariable<f32>,
+
+    // Names of the animations of the animation player. If the shot animation name is empty, the
+    // first animation will be used. The rest of the animations are optional, they are not played if
+    // their names are empty.
+    shot_animation: InheritableVariable<String>,
+    reload_animation: InheritableVariable<String>,
+    dry_fire_animation: InheritableVariable<String>,
+    holster_animation: InheritableVariable<String>,
+    draw_animation: InheritableVariable<String>,
+
+    // Bots within this distance will hear the shots.
+    noise_radius: InheritableVariable<f32>,
+
+    // Remaining time of the reload, the weapon is reloading while it is positive.
+    #[reflect(hidden)]
+    reload_timer: f32,
+    // ANCHOR_END: ammo_fields
+
+    // ANCHOR: spread_fields
+    // Angles of the spread cone (in degrees). Each shot widens the cone up to the maximum, and it
+    // shrinks back over time.
+    min_spread: InheritableVariable<f32>,
+    max_spread: InheritableVariable<f32>,
+    spread_per_shot: InheritableVariable<f32>,
+    spread_recovery: InheritableVariable<f32>,
+
+    // The spread is multiplied by this value while aiming down the sights.
+    aim_spread_scale: InheritableVariable<f32>,
+
+    // Extra spread accumulated by sustained fire (in degrees).
+    #[reflect(hidden)]
+    spread: f32,
+    // ANCHOR_END: spread_fields
+
+    // ANCHOR: recoil_fields
+    // Camera kicks (pitch and yaw, in degrees) for consecutive shots. The last kick is repeated if
+    // the burst is longer than the pattern.
+    recoil_pattern: InheritableVariable<Vec<Vector2<f32>>>,
+
+    // The pattern starts over if there were no shots for this time (in seconds).
+    recoil_reset_time: InheritableVariable<f32>,
+
+    #[reflect(hidden)]
+    recoil_index: usize,
+
+    #[reflect(hidden)]
+    time_since_shot: f32,
+    // ANCHOR_END: recoil_fields
+
+    // ANCHOR: sight_fields
+    // A node, that is aligned with the center of the screen while aiming down the sights.
+    sight: InheritableVariable<Handle<Node>>,
+
+    // Field of view of the camera while aiming (in degrees).
+    aim_fov: InheritableVariable<f32>,
+    // ANCHOR_END: sight_fields
+
+    // ANCHOR: sound_fields
+    shot_sound: InheritableVariable<Option<SoundBufferResource>>,
+
+    // Name of the audio bus, that plays the sounds of the weapon.
+    audio_bus: InheritableVariable<String>,
+    // ANCHOR_END: sound_fields
+}
+
+impl Default for Weapon {
+    fn default() -> Self {
+        Self {
+            projectile: Default::default(),
+            projectile_pool_size: 16.into(),
+            projectile_mode: Default::default(),
+            shot_point: Default::default(),
+            shot_interval: Default::default(),
+            shot_timer: 0.0,
+            animation_player: Default::default(),
+            damage: 10.0.into(),
+            owner: Default::default(),
+            magazine_size: 30.into(),
+            loaded_ammo: 30.into(),
+            reserve_ammo: 90.into(),
+            reload_time: 2.0.into(),
+            shot_animation: Default::default(),
+            reload_animation: Default::default(),
+            dry_fire_animation: Default::default(),
+            holster_animation: Default::default(),
+            draw_animation: Default::default(),
+            noise_radius: 30.0.into(),
+            reload_timer: 0.0,
+            min_spread: 0.5.into(),
+            max_spread: 6.0.into(),
+            spread_per_shot: 0.75.into(),
+            spread_recovery: 6.0.into(),
+            aim_spread_scale: 0.25.into(),
+            spread: 0.0,
+            recoil_pattern: vec![
+                Vector2::new(1.0, 0.0),
+                Vector2::new(1.2, 0.2),
+                Vector2::new(1.4, -0.3),
+                Vector2::new(1.2, 0.4),
+                Vector2::new(1.0, -0.4),
+            ]
+            .into(),
+            recoil_reset_time: 0.4.into(),
+            recoil_index: 0,
+            time_since_shot: 0.0,
+            sight: Default::default(),
+            aim_fov: 45.0.into(),
+            shot_sound: Default::default(),
+            audio_bus: "Primary".to_string().into(),
+        }
+    }
+}
+
+impl Weapon {
+    pub fn set_owner(&mut self, owner: Handle<Node>) {
+        self.owner = owner;
+    }
+
+    pub fn loaded_ammo(&self) -> u32 {
+        *self.loaded_ammo
+    }
+
+    pub fn reserve_ammo(&self) -> u32 {
+        *self.reserve_ammo
+    }
+
+    pub fn projectile(&self) -> Option<&ModelResource> {
+        self.projectile.as_ref()
+    }
+
+    pub fn add_magazine(&mut self) {
+        *self.reserve_ammo += *self.magazine_size;
+    }
+
+    pub fn add_ammo(&mut self, amount: u32) {
+        *self.reserve_ammo += amount;
+    }
+
+    pub fn is_reloading(&self) -> bool {
+        self.reload_timer > 0.0
+    }
+
+    pub fn sight(&self) -> Handle<Node> {
+        *self.sight
+    }
+
+    pub fn aim_fov(&self) -> f32 {
+        *self.aim_fov
+    }
+
+    // ANCHOR: spread
+    // Current angle of the spread cone (in degrees).
+    pub fn spread_angle(&self, aiming: bool) -> f32 {
+        let spread = (*self.min_spread + self.spread).min(*self.max_spread);
+        if aiming {
+            spread * *self.aim_spread_scale
+        } else {
+            spread
+        }
+    }
+
+    // Picks a random direction inside the spread cone around the look vector of the weapon.
+    fn spread_direction(&self, weapon: &Node, aiming: bool) -> Vector3<f32> {
+        let mut rng = thread_rng();
+        // Square root makes the distribution uniform over the cone's base.
+        let radius = self.spread_angle(aiming).to_radians() * rng.gen_range(0.0f32..1.0).sqrt();
+        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+
+        let rotation = UnitQuaternion::from_axis_angle(
+            &UnitVector3::new_normalize(weapon.up_vector()),
+            radius * angle.cos(),
+        ) * UnitQuaternion::from_axis_angle(
+            &UnitVector3::new_normalize(weapon.side_vector()),
+            radius * angle.sin(),
+        );
+
+        rotation * weapon.look_vector()
+    }
+    // ANCHOR_END: spread
+
+    // ANCHOR: next_recoil_kick
+    fn next_recoil_kick(&mut self) -> Option<Vector2<f32>> {
+        let last = self.recoil_pattern.len().checked_sub(1)?;
+        let kick = self.recoil_pattern[self.recoil_index.min(last)];
+        self.recoil_index += 1;
+        Some(kick)
+    }
+    // ANCHOR_END: next_recoil_kick
+
+    // ANCHOR: play_animation
+    fn play_animation(
+        &self,
+        name: &str,
+        self_handle: Handle<Node>,
+        graph: &mut Graph,
+    ) -> GameResult {
+        let animation_player = graph.try_get_mut::<AnimationPlayer>(*self.animation_player)?;
+        let animations = animation_player.animations_mut().get_value_mut_silent();
+
+        let animation = if name.is_empty() {
+            animations.iter_mut().next()
+        } else {
+            animations
+                .find_by_name_mut(name)
+                .map(|(_, animation)| animation)
+        };
+
+        if let Some(animation) = animation {
+            animation.rewind();
+            animation.set_enabled(true);
+        } else {
+            Log::warn(format!(
+                "Weapon {self_handle} does not have {name} animation!"
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn play_optional_animation(
+        &self,
+        name: &str,
+        self_handle: Handle<Node>,
+        graph: &mut Graph,
+    ) -> GameResult {
+        if name.is_empty() {
+            return Ok(());
+        }
+        self.play_animation(name, self_handle, graph)
+    }
+    // ANCHOR_END: play_animation
+
+    // ANCHOR: begin_reload
+    fn begin_reload(&mut self, self_handle: Handle<Node>, graph: &mut Graph) -> GameResult {
+        if self.is_reloading()
+            || *self.reserve_ammo == 0
+            || *self.loaded_ammo >= *self.magazine_size
+        {
+            return Ok(());
+        }
+
+        self.reload_timer = *self.reload_time;
+
+        self.play_optional_animation(&self.reload_animation, self_handle, graph)
+    }
+    // ANCHOR_END: begin_reload
+
+    // ANCHOR: finish_reload
+    fn finish_reload(&mut self) {
+        let needed = self.magazine_size.saturating_sub(*self.loaded_ammo);
+        let taken = needed.min(*self.reserve_ammo);
+        *self.reserve_ammo -= taken;
+        *self.loaded_ammo += taken;
+    }
+    // ANCHOR_END: finish_reload
 }
 
 // ANCHOR: shoot_message
 #[derive(Debug)]
-pub struct ShootWeaponMessage {}
+pub struct ShootWeaponMessage {
+    // Aiming down the sights reduces the spread.
+    pub aiming: bool,
+}
 impl ScriptMessagePayload for ShootWeaponMessage {}
 // ANCHOR_END: shoot_message
 
+// ANCHOR: recoil_message
+// The weapon sends this message to its owner on every shot to kick the camera.
+#[derive(Debug)]
+pub struct RecoilMessage {
+    // Angles in degrees, positive pitch kicks the camera up.
+    pub pitch: f32,
+    pub yaw: f32,
+}
+impl ScriptMessagePayload for RecoilMessage {}
+// ANCHOR_END: recoil_message
+
+// ANCHOR: reload_message
+#[derive(Debug)]
+pub struct ReloadWeaponMessage {}
+impl ScriptMessagePayload for ReloadWeaponMessage {}
+// ANCHOR_END: reload_message
+
+// ANCHOR: switch_messages
+#[derive(Debug)]
+pub struct HolsterWeaponMessage {}
+impl ScriptMessagePayload for HolsterWeaponMessage {}
+
+#[derive(Debug)]
+pub struct DrawWeaponMessage {}
+impl ScriptMessagePayload for DrawWeaponMessage {}
+// ANCHOR_END: switch_messages
+
 impl ScriptTrait for Weapon {
     // ANCHOR: on_start
     fn on_start(&mut self, context: &mut ScriptContext) -> GameResult {
         context
             .message_dispatcher
             .subscribe_to::<ShootWeaponMessage>(context.handle);
+        context
+            .message_dispatcher
+            .subscribe_to::<ReloadWeaponMessage>(context.handle);
+        context
+            .message_dispatcher
+            .subscribe_to::<HolsterWeaponMessage>(context.handle);
+        context
+            .message_dispatcher
+            .subscribe_to::<DrawWeaponMessage>(context.handle);
+
+        // ANCHOR: prewarm_projectiles
+        if let Some(projectile) = self.projectile.as_ref() {
+            context.plugins.get_mut::<Game>().pools.prewarm(
+                projectile,
+                *self.projectile_pool_size,
+                context.scene,
+            );
+        }
+        // ANCHOR_END: prewarm_projectiles
         Ok(())
     }
     // ANCHOR_END: on_start
 
     // ANCHOR: on_update
     fn on_update(&mut self, context: &mut ScriptContext) -> GameResult {
+        // Menus are opened or the server simulates the world, it is frozen here.
+        if !context.plugins.get::<Game>().is_simulating() {
+            return Ok(());
+        }
+
         self.shot_timer -= context.dt;
+
+        // ANCHOR: spread_recovery
+        self.spread = (self.spread - *self.spread_recovery * context.dt).max(0.0);
+
+        self.time_since_shot += context.dt;
+        if self.time_since_shot >= *self.recoil_reset_time {
+            self.recoil_index = 0;
+        }
+        // ANCHOR_END: spread_recovery
+
+        // ANCHOR: reload_timer
+        if self.is_reloading() {
+            self.reload_timer -= context.dt;
+            if !self.is_reloading() {
+                self.finish_reload();
+            }
+        }
+        // ANCHOR_END: reload_timer
+
         Ok(())
     }
     // ANCHOR_END: on_update
@@ -63,54 +434,169 @@ impl ScriptTrait for Weapon {
         message: &mut dyn ScriptMessagePayload,
         ctx: &mut ScriptMessageContext,
     ) -> GameResult {
+        // ANCHOR: reload_message_handling
+        if message.downcast_ref::<ReloadWeaponMessage>().is_some() {
+            return self.begin_reload(ctx.handle, &mut ctx.scene.graph);
+        }
+        // ANCHOR_END: reload_message_handling
+
+        // ANCHOR: switch_message_handling
+        if message.downcast_ref::<HolsterWeaponMessage>().is_some() {
+            // Holstering interrupts reloading, the magazine stays as is.
+            self.reload_timer = 0.0;
+            return self.play_optional_animation(
+                &self.holster_animation,
+                ctx.handle,
+                &mut ctx.scene.graph,
+            );
+        }
+
+        if message.downcast_ref::<DrawWeaponMessage>().is_some() {
+            return self.play_optional_animation(
+                &self.draw_animation,
+                ctx.handle,
+                &mut ctx.scene.graph,
+            );
+        }
+        // ANCHOR_END: switch_message_handling
+
         // Check if we've received an appropriate message. This is needed because message channel is
         // common across all scripts.
-        if message.downcast_ref::<ShootWeaponMessage>().is_some() {
+        if let Some(shoot) = message.downcast_ref::<ShootWeaponMessage>() {
             // ANCHOR_END: on_message_begin
 
             // ANCHOR: shooting_condition
-            if self.shot_timer >= 0.0 {
+            if self.shot_timer >= 0.0 || self.is_reloading() {
                 return Ok(());
             }
             // Reset the timer, this way the next shot cannot be done earlier than the interval.
             self.shot_timer = *self.shot_interval;
             // ANCHOR_END: shooting_condition
 
-            // ANCHOR: recoil_animation
-            let animation_player = ctx
-                .scene
-                .graph
-                .try_get_mut::<AnimationPlayer>(*self.animation_player)?;
-
-            if let Some(animation) = animation_player
-                .animations_mut()
-                .get_value_mut_silent()
-                .iter_mut()
-                .next()
-            {
-                animation.rewind();
-                animation.set_enabled(true);
+            // ANCHOR: dry_fire
+            // An empty magazine just clicks and starts reloading if there's some ammo left.
+            if *self.loaded_ammo == 0 {
+                self.play_optional_animation(
+                    &self.dry_fire_animation,
+                    ctx.handle,
+                    &mut ctx.scene.graph,
+                )?;
+                return self.begin_reload(ctx.handle, &mut ctx.scene.graph);
             }
+            *self.loaded_ammo -= 1;
+            // ANCHOR_END: dry_fire
 
+            // ANCHOR: recoil_animation
+            self.play_animation(&self.shot_animation, ctx.handle, &mut ctx.scene.graph)?;
             // ANCHOR_END: recoil_animation
 
+            // ANCHOR: recoil_kick
+            if let Some(kick) = self.next_recoil_kick() {
+                ctx.message_sender.send_to_target(
+                    self.owner,
+                    RecoilMessage {
+                        pitch: kick.x,
+                        yaw: kick.y,
+                    },
+                );
+            }
+            self.time_since_shot = 0.0;
+            // ANCHOR_END: recoil_kick
+
             // ANCHOR: on_message_end
             if let Some(projectile_prefab) = self.projectile.as_ref() {
                 // Try to get the position of the shooting point.
                 let shot_point = ctx.scene.graph.try_get(*self.shot_point)?.global_position();
 
-                // Shooting direction is just a direction of the weapon (its look vector)
-                let direction = ctx.scene.graph[ctx.handle].look_vector();
+                // ANCHOR: noise
+                // Shots are loud, let the bots nearby know about it.
+                ctx.message_sender.send_global(NoiseMessage {
+                    position: shot_point,
+                    radius: *self.noise_radius,
+                });
+                // ANCHOR_END: noise
 
-                // Finally instantiate our projectile at the position and direction.
-                projectile_prefab.instantiate_at(
-                    ctx.scene,
+                // ANCHOR: shot_sound
+                if let Some(shot_sound) = self.shot_sound.as_ref() {
+                    play_sound(
+                        shot_sound.clone(),
+                        shot_point,
+                        &self.audio_bus,
+                        &mut ctx.scene.graph,
+                    );
+                }
+                // ANCHOR_END: shot_sound
+
+                // Shooting direction is a direction of the weapon (its look vector) deviated
+                // randomly within the spread cone. Sustained fire widens the cone.
+                let direction = self.spread_direction(&ctx.scene.graph[ctx.handle], shoot.aiming);
+                self.spread =
+                    (self.spread + *self.spread_per_shot).min(*self.max_spread - *self.min_spread);
+
+                // Finally take a projectile from the pool and put it at the position and direction.
+                let rotation = math::vector_to_quat(direction);
+                let projectile = ctx.plugins.get_mut::<Game>().pools.spawn(
+                    projectile_prefab,
                     shot_point,
-                    math::vector_to_quat(direction),
+                    rotation,
+                    ctx.scene,
                 );
+
+                // ANCHOR: pass_damage
+                // Pass the damage to the projectile, it will deliver it to the victim on hit.
+                if let Some(projectile) = ctx
+                    .scene
+                    .graph
+                    .try_get_mut(projectile)?
+                    .try_get_script_mut::<Projectile>()
+                {
+                    projectile.damage = *self.damage;
+                    projectile.owner = self.owner;
+                    projectile.mode = *self.projectile_mode;
+                    projectile.reset();
+                }
+                // ANCHOR_END: pass_damage
+
+                // Network clients show their own copies of the hitscan shots.
+                if *self.projectile_mode == ProjectileMode::Hitscan {
+                    let weapon = ctx.scene.graph.try_get(ctx.handle)?.instance_id();
+                    if let Some(deathmatch) = ctx.plugins.get_mut::<Game>().deathmatch.as_mut() {
+                        deathmatch.on_shot(weapon, shot_point, rotation);
+                    }
+                }
             }
         }
         Ok(())
     }
     // ANCHOR_END: on_message_end
 }
+
+// Weapons in a known state for the save test, see `save.rs`.
+#[cfg(test)]
+impl Weapon {
+    // A weapon in the middle of a reload, right after a burst.
+    pub(crate) fn mid_fight(owner: Handle<Node>) -> Self {
+        let mut weapon = Self {
+            owner,
+            shot_timer: 0.05,
+            reload_timer: 1.2,
+            spread: 2.5,
+            recoil_index: 3,
+            time_since_shot: 0.05,
+            ..Default::default()
+        };
+        weapon.loaded_ammo.set_value_and_mark_modified(7);
+        weapon.reserve_ammo.set_value_and_mark_modified(23);
+        weapon
+    }
+
+    // The fields are hidden from the reflection, so they are compared one by one.
+    pub(crate) fn assert_same_runtime_state(&self, other: &Self) {
+        assert_eq!(self.owner, other.owner);
+        assert_eq!(self.shot_timer, other.shot_timer);
+        assert_eq!(self.reload_timer, other.reload_timer);
+        assert_eq!(self.spread, other.spread);
+        assert_eq!(self.recoil_index, other.recoil_index);
+        assert_eq!(self.time_since_shot, other.time_since_shot);
+    }
+}
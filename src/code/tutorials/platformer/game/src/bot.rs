@@ -184,6 +184,11 @@ impl Bot {
 impl ScriptTrait for Bot {
     // ANCHOR: search_target_call
     fn on_update(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        // Menus are opened, the world is frozen.
+        if !ctx.plugins.get::<Game>().flow.is_playing() {
+            return Ok(());
+        }
+
         self.search_target(ctx)?;
         // ANCHOR_END: search_target_call
 
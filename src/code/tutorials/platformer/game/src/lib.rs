@@ -5,46 +5,70 @@ mod residuals;
 
 // ANCHOR: imports
 use crate::bot::Bot;
-use fyrox::plugin::SceneLoaderResult;
+use common::{
+    character::{find_collider, CharacterController, CharacterInput, CharacterShape, Physics2d},
+    flow::{FlowPlugin, GameFlow},
+    input::{Binding, InputMap},
+};
 use fyrox::{
     core::{
-        algebra::Vector2, pool::Handle, reflect::prelude::*, type_traits::prelude::*,
+        algebra::{Vector2, Vector3},
+        pool::Handle,
+        reflect::prelude::*,
+        type_traits::prelude::*,
         visitor::prelude::*,
     },
-    event::{ElementState, Event, WindowEvent},
+    event::Event,
     graph::SceneGraph,
-    keyboard::{KeyCode, PhysicalKey},
+    gui::{message::UiMessage, UserInterface},
+    keyboard::KeyCode,
     plugin::{error::GameResult, Plugin, PluginContext, PluginRegistrationContext},
     scene::{
         animation::spritesheet::SpriteSheetAnimation,
         dim2::{rectangle::Rectangle, rigidbody::RigidBody},
         node::Node,
-        Scene,
     },
     script::{ScriptContext, ScriptTrait},
 };
 use std::path::Path;
 // ANCHOR_END: imports
 
+// ANCHOR: input_config
+// Player's key bindings, missing actions are taken from the default bindings below.
+const INPUT_CONFIG_PATH: &str = "data/input.ron";
+
+fn default_input_map() -> InputMap {
+    InputMap::default()
+        .with_action("move_left", [Binding::Key(KeyCode::KeyA)])
+        .with_action("move_right", [Binding::Key(KeyCode::KeyD)])
+        .with_action("jump", [Binding::Key(KeyCode::Space)])
+        .with_action("pause", [Binding::Key(KeyCode::Escape)])
+}
+// ANCHOR_END: input_config
+
 #[derive(Visit, Reflect, Debug, Default, Clone)]
 pub struct Game {
-    scene: Handle<Scene>,
+    // Owns the level scene and switches between the menu, the game and the pause.
+    pub flow: GameFlow,
 
     // ANCHOR: player_field
     player: Handle<Node>,
     // ANCHOR_END: player_field
+
+    // Scripts read this to check actions instead of hard-coded keys.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub input: InputMap,
 }
 
-impl Game {
-    fn on_scene_loading_result(
-        &mut self,
-        result: SceneLoaderResult,
-        ctx: &mut PluginContext,
-    ) -> GameResult {
-        if self.scene.is_some() {
-            ctx.scenes.remove(self.scene);
-        }
-        self.scene = ctx.scenes.add(result?.payload);
+impl FlowPlugin for Game {
+    fn flow_mut(&mut self) -> &mut GameFlow {
+        &mut self.flow
+    }
+
+    fn on_level_loaded(&mut self, _ctx: &mut PluginContext) -> GameResult {
+        // The player of the new level will register itself.
+        self.player = Handle::NONE;
         Ok(())
     }
 }
@@ -60,17 +84,38 @@ impl Plugin for Game {
         Ok(())
     }
 
+    fn on_loaded(&mut self, _context: PluginContext) -> GameResult {
+        // Input map is not serialized, so it must be reloaded after hot reloading.
+        self.input = InputMap::load_or(INPUT_CONFIG_PATH, default_input_map());
+        Ok(())
+    }
+
     fn init(&mut self, scene_path: Option<&str>, mut ctx: PluginContext) -> GameResult {
-        ctx.load_scene(
-            scene_path.unwrap_or("data/scene.rgs"),
-            false,
-            |result, game: &mut Game, ctx| game.on_scene_loading_result(result, ctx),
-        );
+        self.input = InputMap::load_or(INPUT_CONFIG_PATH, default_input_map());
+
+        self.flow = GameFlow::new("data/scene.rgs");
+        self.flow.init::<Game>(scene_path, &mut ctx);
+        Ok(())
+    }
+
+    fn on_os_event(&mut self, event: &Event<()>, mut ctx: PluginContext) -> GameResult {
+        self.flow
+            .on_os_event(event, self.input.bindings("pause"), &mut ctx);
+        Ok(())
+    }
+
+    fn on_ui_message(
+        &mut self,
+        ctx: &mut PluginContext,
+        message: &UiMessage,
+        _ui_handle: Handle<UserInterface>,
+    ) -> GameResult {
+        self.flow.on_ui_message::<Game>(message, ctx);
         Ok(())
     }
 
     fn update(&mut self, context: &mut PluginContext) -> GameResult {
-        if let Ok(scene) = context.scenes.try_get_mut(self.scene) {
+        if let Ok(scene) = context.scenes.try_get_mut(self.flow.scene()) {
             scene.drawing_context.clear_lines();
             // scene.graph.physics2d.draw(&mut scene.drawing_context);
         }
@@ -91,6 +136,10 @@ struct Player {
     move_left: bool,
     move_right: bool,
     jump: bool,
+
+    // Collider of the player, the character controller uses its shape to find the ground.
+    collider: Handle<Node>,
+    controller: CharacterController,
     // ANCHOR_END: movement_fields
 
     // ANCHOR: animation_fields
@@ -108,6 +157,8 @@ impl Default for Player {
             move_left: false,
             move_right: false,
             jump: false,
+            collider: Handle::NONE,
+            controller: Default::default(),
             // ANCHOR: animation_fields_defaults_end
             // ...
             animations: Default::default(),
@@ -117,44 +168,36 @@ impl Default for Player {
 }
 // ANCHOR_END: animation_fields_defaults_end
 
+// ANCHOR: read_input
+impl Player {
+    // Reads the state of the actions, that are bound to the keys in the input config of the game.
+    fn read_input(&mut self, context: &ScriptContext) {
+        let input = &context.plugins.get::<Game>().input;
+        self.move_left = input.is_pressed("move_left", context.input_state);
+        self.move_right = input.is_pressed("move_right", context.input_state);
+        self.jump = input.is_pressed("jump", context.input_state);
+    }
+}
+// ANCHOR_END: read_input
+
 impl ScriptTrait for Player {
     // ANCHOR: set_player_field
     fn on_start(&mut self, ctx: &mut ScriptContext) -> GameResult {
         ctx.plugins.get_mut::<Game>().player = ctx.handle;
+        self.collider = find_collider(&ctx.scene.graph, ctx.handle, self.collider);
         Ok(())
     }
     // ANCHOR_END: set_player_field
 
-    // ANCHOR: on_os_event
-    // Called everytime when there is an event from OS (mouse click, key press, etc.)
-    fn on_os_event(&mut self, event: &Event<()>, _context: &mut ScriptContext) -> GameResult {
-        if let Event::WindowEvent { event, .. } = event {
-            if let WindowEvent::KeyboardInput { event, .. } = event {
-                if let PhysicalKey::Code(keycode) = event.physical_key {
-                    let is_pressed = event.state == ElementState::Pressed;
-
-                    match keycode {
-                        KeyCode::KeyA => self.move_left = is_pressed,
-                        KeyCode::KeyD => self.move_right = is_pressed,
-                        KeyCode::Space => self.jump = is_pressed,
-                        _ => (),
-                    }
-                }
-            }
-        }
-        Ok(())
-    }
-    // ANCHOR_END: on_os_event
-
     // Called every frame at fixed rate of 60 FPS.
     // ANCHOR: on_update_begin
     fn on_update(&mut self, context: &mut ScriptContext) -> GameResult {
-        // The script can be assigned to any scene node, but we assert that it will work only with
-        // 2d rigid body nodes.
-        let rigid_body = context
-            .scene
-            .graph
-            .try_get_mut_of_type::<RigidBody>(context.handle)?;
+        // Menus are opened, the world is frozen.
+        if !context.plugins.get::<Game>().flow.is_playing() {
+            return Ok(());
+        }
+
+        self.read_input(context);
 
         let x_speed = if self.move_left {
             3.0
@@ -164,11 +207,48 @@ impl ScriptTrait for Player {
             0.0
         };
 
-        if self.jump {
-            rigid_body.set_lin_vel(Vector2::new(x_speed, 4.0))
+        // The character controller moves the player, it needs the shape of the collider to find
+        // the ground under the feet.
+        if let Some(shape) = CharacterShape::from_collider_2d(&context.scene.graph, self.collider) {
+            // The script can be assigned to any scene node, but we assert that it will work only
+            // with 2d rigid body nodes.
+            let velocity = context
+                .scene
+                .graph
+                .try_get_of_type::<RigidBody>(context.handle)?
+                .lin_vel();
+
+            // The player could jump only from the ground.
+            let movement = self.controller.update(
+                &Physics2d::new(&context.scene.graph, context.handle),
+                shape,
+                CharacterInput {
+                    desired_velocity: Vector3::new(x_speed, 0.0, 0.0),
+                    jump_speed: self.jump.then_some(4.0),
+                },
+                Vector3::new(velocity.x, velocity.y, 0.0),
+                context.dt,
+            );
+            movement.apply_2d(
+                context
+                    .scene
+                    .graph
+                    .try_get_mut_of_type::<RigidBody>(context.handle)?,
+            );
         } else {
-            rigid_body.set_lin_vel(Vector2::new(x_speed, rigid_body.lin_vel().y))
-        };
+            // Without a collider the velocity is set directly, like before the character
+            // controller.
+            let rigid_body = context
+                .scene
+                .graph
+                .try_get_mut_of_type::<RigidBody>(context.handle)?;
+
+            if self.jump {
+                rigid_body.set_lin_vel(Vector2::new(x_speed, 4.0))
+            } else {
+                rigid_body.set_lin_vel(Vector2::new(x_speed, rigid_body.lin_vel().y))
+            };
+        }
         // ...
         // ANCHOR_END: on_update_begin
 
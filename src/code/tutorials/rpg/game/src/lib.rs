@@ -1,32 +1,78 @@
 //! Game project.
 use crate::player::Player;
+use common::{
+    flow::{FlowPlugin, GameFlow},
+    input::{AxisBinding, Binding, InputMap},
+    interaction::{Door, Elevator, Switch},
+};
 use fyrox::plugin::error::GameResult;
-use fyrox::plugin::SceneLoaderResult;
 use fyrox::{
     core::pool::Handle,
     core::{reflect::prelude::*, visitor::prelude::*},
+    event::Event,
+    gui::{
+        message::UiMessage,
+        text::{Text, TextBuilder, TextMessage},
+        widget::{WidgetBuilder, WidgetMessage},
+        HorizontalAlignment, Thickness, UserInterface, VerticalAlignment,
+    },
+    keyboard::KeyCode,
     plugin::{Plugin, PluginContext, PluginRegistrationContext},
-    scene::Scene,
 };
 
 mod player;
 
+// ANCHOR: input_config
+// Player's key bindings, missing actions are taken from the default bindings below.
+const INPUT_CONFIG_PATH: &str = "data/input.ron";
+
+fn default_input_map() -> InputMap {
+    InputMap::default()
+        .with_action("walk_forward", [Binding::Key(KeyCode::KeyW)])
+        .with_action("walk_backward", [Binding::Key(KeyCode::KeyS)])
+        .with_action("walk_left", [Binding::Key(KeyCode::KeyA)])
+        .with_action("walk_right", [Binding::Key(KeyCode::KeyD)])
+        .with_action("interact", [Binding::Key(KeyCode::KeyE)])
+        .with_action("pause", [Binding::Key(KeyCode::Escape)])
+        .with_axis(
+            "look_horizontal",
+            [AxisBinding::MouseX { sensitivity: 0.2 }],
+        )
+        .with_axis("look_vertical", [AxisBinding::MouseY { sensitivity: 0.2 }])
+}
+// ANCHOR_END: input_config
+
 #[derive(Visit, Reflect, Clone, Default, Debug)]
 pub struct Game {
-    scene: Handle<Scene>,
+    // Owns the level scene and switches between the menu, the game and the pause.
+    pub flow: GameFlow,
+
+    // Scripts read this to check actions instead of hard-coded keys.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub input: InputMap,
+
+    // ANCHOR: prompt_fields
+    // Tells what happens if the player uses the object in front of them, the player sets it every
+    // frame.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    pub prompt: Option<String>,
+
+    #[visit(skip)]
+    #[reflect(hidden)]
+    prompt_text: Handle<Text>,
+
+    // The prompt, that is currently shown, the text is updated only when the prompt changes.
+    #[visit(skip)]
+    #[reflect(hidden)]
+    shown_prompt: Option<String>,
+    // ANCHOR_END: prompt_fields
 }
 
-impl Game {
-    fn on_scene_loading_result(
-        &mut self,
-        result: SceneLoaderResult,
-        ctx: &mut PluginContext,
-    ) -> GameResult {
-        if self.scene.is_some() {
-            ctx.scenes.remove(self.scene);
-        }
-        self.scene = ctx.scenes.add(result?.payload);
-        Ok(())
+impl FlowPlugin for Game {
+    fn flow_mut(&mut self) -> &mut GameFlow {
+        &mut self.flow
     }
 }
 
@@ -37,17 +83,83 @@ impl Plugin for Game {
             .serialization_context
             .script_constructors
             .add::<Player>("Player");
+
+        // ANCHOR: interaction_script_reg
+        context
+            .serialization_context
+            .script_constructors
+            .add::<Door>("Door");
+        context
+            .serialization_context
+            .script_constructors
+            .add::<Switch>("Switch");
+        context
+            .serialization_context
+            .script_constructors
+            .add::<Elevator>("Elevator");
+        // ANCHOR_END: interaction_script_reg
         Ok(())
     }
     // ANCHOR_END: register
 
+    fn on_loaded(&mut self, _context: PluginContext) -> GameResult {
+        // Input map is not serialized, so it must be reloaded after hot reloading.
+        self.input = InputMap::load_or(INPUT_CONFIG_PATH, default_input_map());
+        Ok(())
+    }
+
     fn init(&mut self, scene_path: Option<&str>, mut context: PluginContext) -> GameResult {
-        context.load_scene(
-            scene_path.unwrap_or("data/scene.rgs"),
-            false,
-            |result, game: &mut Game, ctx| game.on_scene_loading_result(result, ctx),
-        );
+        self.input = InputMap::load_or(INPUT_CONFIG_PATH, default_input_map());
+
+        self.flow = GameFlow::new("data/scene.rgs");
+        self.flow.init::<Game>(scene_path, &mut context);
+
+        // ANCHOR: prompt_text
+        let ui = context.user_interfaces.first_mut();
+        self.prompt_text = TextBuilder::new(
+            WidgetBuilder::new()
+                .with_visibility(false)
+                .with_horizontal_alignment(HorizontalAlignment::Center)
+                .with_vertical_alignment(VerticalAlignment::Bottom)
+                .with_margin(Thickness::bottom(40.0)),
+        )
+        .build(&mut ui.build_ctx());
+        // ANCHOR_END: prompt_text
         Ok(())
     }
 
+    // ANCHOR: update_prompt
+    fn update(&mut self, context: &mut PluginContext) -> GameResult {
+        // The prompt is hidden while the menus are opened.
+        let prompt = self.prompt.take().filter(|_| self.flow.is_playing());
+        if prompt != self.shown_prompt {
+            let ui = context.user_interfaces.first_mut();
+            ui.send(
+                self.prompt_text,
+                WidgetMessage::Visibility(prompt.is_some()),
+            );
+            if let Some(prompt) = prompt.clone() {
+                ui.send(self.prompt_text, TextMessage::Text(prompt));
+            }
+            self.shown_prompt = prompt;
+        }
+        Ok(())
+    }
+    // ANCHOR_END: update_prompt
+
+    fn on_os_event(&mut self, event: &Event<()>, mut context: PluginContext) -> GameResult {
+        self.flow
+            .on_os_event(event, self.input.bindings("pause"), &mut context);
+        Ok(())
+    }
+
+    fn on_ui_message(
+        &mut self,
+        context: &mut PluginContext,
+        message: &UiMessage,
+        _ui_handle: Handle<UserInterface>,
+    ) -> GameResult {
+        self.flow.on_ui_message::<Game>(message, context);
+        Ok(())
+    }
 }
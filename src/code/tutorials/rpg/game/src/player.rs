@@ -1,3 +1,8 @@
+use crate::Game;
+use common::{
+    character::{find_collider, CharacterController, CharacterInput, CharacterShape, Physics3d},
+    interaction::Interactor,
+};
 use fyrox::plugin::error::GameResult;
 use fyrox::{
     core::{
@@ -11,9 +16,7 @@ use fyrox::{
         visitor::prelude::*,
         TypeUuidProvider,
     },
-    event::{DeviceEvent, ElementState, Event, WindowEvent},
     graph::SceneGraph,
-    keyboard::{KeyCode, PhysicalKey},
     scene::{animation::absm::prelude::*, node::Node, rigidbody::RigidBody},
     script::{ScriptContext, ScriptTrait},
 };
@@ -34,6 +37,14 @@ pub struct Player {
 
     model_yaw: InheritableVariable<SmoothAngle>,
 
+    // Collider of the character, the controller uses its shape to find the ground and obstacles.
+    collider: InheritableVariable<Handle<Node>>,
+
+    controller: CharacterController,
+
+    // Finds doors, switches and other usable objects in front of the camera.
+    interactor: Interactor,
+
     #[reflect(hidden)]
     #[visit(skip)]
     walk_forward: bool,
@@ -68,40 +79,59 @@ impl TypeUuidProvider for Player {
     }
 }
 
+// ANCHOR: read_input
+impl Player {
+    // Reads the state of the actions, that are bound to the keys and mouse axes in the input config
+    // of the game.
+    fn read_input(&mut self, ctx: &ScriptContext) {
+        let input = &ctx.plugins.get::<Game>().input;
+        self.walk_forward = input.is_pressed("walk_forward", ctx.input_state);
+        self.walk_backward = input.is_pressed("walk_backward", ctx.input_state);
+        self.walk_left = input.is_pressed("walk_left", ctx.input_state);
+        self.walk_right = input.is_pressed("walk_right", ctx.input_state);
+
+        let mouse_sens = ctx.dt;
+        self.yaw -= input.axis("look_horizontal", ctx.input_state) * mouse_sens;
+        self.pitch = (self.pitch + input.axis("look_vertical", ctx.input_state) * mouse_sens)
+            .clamp(-90.0f32.to_radians(), 90.0f32.to_radians());
+    }
+}
+// ANCHOR_END: read_input
+
 impl ScriptTrait for Player {
-    // ANCHOR: on_os_event
-    fn on_os_event(&mut self, event: &Event<()>, ctx: &mut ScriptContext) -> GameResult {
-        match event {
-            Event::WindowEvent { event, .. } => {
-                if let WindowEvent::KeyboardInput { event, .. } = event {
-                    if let PhysicalKey::Code(code) = event.physical_key {
-                        let pressed = event.state == ElementState::Pressed;
-                        match code {
-                            KeyCode::KeyW => self.walk_forward = pressed,
-                            KeyCode::KeyS => self.walk_backward = pressed,
-                            KeyCode::KeyA => self.walk_left = pressed,
-                            KeyCode::KeyD => self.walk_right = pressed,
-                            _ => (),
-                        }
-                    }
-                }
-            }
-            Event::DeviceEvent { event, .. } => {
-                if let DeviceEvent::MouseMotion { delta } = event {
-                    let mouse_sens = 0.2 * ctx.dt;
-                    self.yaw -= (delta.0 as f32) * mouse_sens;
-                    self.pitch = (self.pitch + (delta.1 as f32) * mouse_sens)
-                        .clamp(-90.0f32.to_radians(), 90.0f32.to_radians());
-                }
-            }
-            _ => (),
-        }
+    fn on_start(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        let collider = find_collider(&ctx.scene.graph, ctx.handle, *self.collider);
+        self.collider.set_value_silent(collider);
         Ok(())
     }
-    // ANCHOR_END: on_os_event
 
     // ANCHOR: on_update
     fn on_update(&mut self, ctx: &mut ScriptContext) -> GameResult {
+        // Menus are opened, the world is frozen.
+        if !ctx.plugins.get::<Game>().flow.is_playing() {
+            return Ok(());
+        }
+
+        // Step 0. Read the input.
+        self.read_input(ctx);
+
+        // Step 0.5. Find a usable object in front of the camera and use it, if asked to.
+        let camera_hinge = ctx.scene.graph.try_get(*self.camera_hinge)?;
+        let prompt = self
+            .interactor
+            .update(
+                ctx.handle,
+                camera_hinge.global_position(),
+                camera_hinge.look_vector(),
+                &ctx.scene.graph,
+            )
+            .map(|prompt| prompt.to_owned());
+        let game = ctx.plugins.get_mut::<Game>();
+        game.prompt = prompt;
+        if game.input.is_just_pressed("interact", ctx.input_state) {
+            self.interactor.interact(ctx.handle, ctx.message_sender);
+        }
+
         // Step 1. Fetch the velocity vector from the animation blending state machine.
         let transform = ctx.scene.graph[*self.model].global_transform();
         let mut velocity = Vector3::default();
@@ -112,13 +142,39 @@ impl ScriptTrait for Player {
                 .scale(1.0 / ctx.dt);
         }
 
-        // Step 2. Apply the velocity to the rigid body and lock rotations.
+        // Step 2. Let the character controller move the rigid body with this velocity and lock
+        // rotations.
+        let movement = match CharacterShape::from_collider(&ctx.scene.graph, *self.collider) {
+            Some(shape) => {
+                let body_velocity = ctx
+                    .scene
+                    .graph
+                    .try_get_of_type::<RigidBody>(ctx.handle)?
+                    .lin_vel();
+                Some(self.controller.update(
+                    &Physics3d::new(&ctx.scene.graph, ctx.handle),
+                    shape,
+                    CharacterInput {
+                        desired_velocity: Vector3::new(velocity.x, 0.0, velocity.z),
+                        jump_speed: None,
+                    },
+                    body_velocity,
+                    ctx.dt,
+                ))
+            }
+            None => None,
+        };
         let body = ctx
             .scene
             .graph
             .try_get_mut_of_type::<RigidBody>(ctx.handle)?;
         body.set_ang_vel(Default::default());
-        body.set_lin_vel(Vector3::new(velocity.x, body.lin_vel().y, velocity.z));
+        match movement {
+            Some(movement) => movement.apply(body),
+            // Without a collider the velocity is set directly, like before the character
+            // controller.
+            None => body.set_lin_vel(Vector3::new(velocity.x, body.lin_vel().y, velocity.z)),
+        }
 
         // Step 3. Rotate the model pivot according to the movement direction.
         let quat_yaw = UnitQuaternion::from_axis_angle(&Vector3::y_axis(), self.yaw);